@@ -1,10 +1,18 @@
-use std::{io::BufRead, rc::Rc};
+pub mod sarif;
 
-use colored::Colorize;
+use std::rc::Rc;
 
+use colored::{ColoredString, Colorize};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+
+use crate::analyzer::source::Source;
+
+/// A place in a [`Source`]: `abs` counts characters and `byte` counts bytes
+/// from its start, `row` and `col` are zero-based.
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub struct Position {
     pub abs: usize,
+    pub byte: usize,
     pub col: usize,
     pub row: usize,
 }
@@ -12,17 +20,60 @@ pub struct Position {
 #[derive(Clone, Debug, Default)]
 pub struct Span {
     pub end: Position,
-    pub source: Rc<String>,
+    pub source: Rc<Source>,
     pub start: Position,
 }
 
 impl Span {
-    pub fn new(source: Rc<String>, start: Position, end: Position) -> Self {
+    pub fn new(source: Rc<Source>, start: Position, end: Position) -> Self {
         Self { end, source, start }
     }
+
+    /// The text the span covers.
+    pub fn text(&self) -> &str {
+        self.source
+            .text
+            .get(self.start.byte..self.end.byte)
+            .unwrap_or_default()
+    }
+}
+
+/// A [`Position`] as it is serialized, with a one-based row and column and
+/// the byte offset into the source.
+#[derive(Serialize)]
+struct Location {
+    byte: usize,
+    col: usize,
+    row: usize,
+}
+
+impl Span {
+    fn location(&self, position: Position) -> Location {
+        Location {
+            byte: position.byte,
+            col: position.col + 1,
+            row: position.row + 1,
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
+impl Serialize for Span {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Span", 3)?;
+
+        state.serialize_field("end", &self.location(self.end))?;
+        state.serialize_field("file", &self.source.name)?;
+        state.serialize_field("start", &self.location(self.start))?;
+
+        state.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Kind {
     Critical,
     Error,
@@ -30,83 +81,326 @@ pub enum Kind {
     Warning,
 }
 
-#[derive(Clone, Debug)]
+/// A secondary span that gives context to a [`Diagnostic`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Label {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Label {
+    #[rustfmt::skip]
+    pub fn new(span: Span, message: &str) -> Self {
+        Self { message: message.to_string(), span }
+    }
+}
+
+/// A message about the input, serialized as one JSON object with its spans.
+#[derive(Clone, Debug, Serialize)]
 pub struct Diagnostic {
+    pub help: Vec<String>,
     pub kind: Kind,
+    pub label: Option<String>,
+    pub labels: Vec<Label>,
     pub message: String,
+    pub notes: Vec<String>,
     pub span: Span,
 }
 
-impl std::fmt::Display for Diagnostic {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// A span to underline, `^` for the primary span and `-` for the labels.
+struct Annotation<'a> {
+    message: Option<&'a str>,
+    primary: bool,
+    span: &'a Span,
+}
+
+/// The last row a span covers; a span ending right after a line terminator
+/// stops on the line before it.
+fn last_row(span: &Span) -> usize {
+    if span.end.row > span.start.row && span.end.col == 0 {
+        span.end.row - 1
+    } else {
+        span.end.row.max(span.start.row)
+    }
+}
+
+/// The rows drawn for a span, eliding the middle of long ones.
+fn rows(span: &Span) -> Vec<usize> {
+    let first = span.start.row;
+    let last = last_row(span);
+
+    if last - first < 6 {
+        (first..=last).collect()
+    } else {
+        vec![first, first + 1, last - 1, last]
+    }
+}
+
+/// The columns of `row` covered by a span, at least one on its first row.
+fn columns(span: &Span, row: usize, width: usize) -> Option<(usize, usize)> {
+    if row < span.start.row || row > last_row(span) {
+        return None;
+    }
+
+    let from = if row == span.start.row {
+        span.start.col
+    } else {
+        0
+    };
+    let to = if row == span.end.row {
+        span.end.col
+    } else {
+        width
+    };
+
+    if to > from {
+        Some((from, to))
+    } else if row == span.start.row {
+        Some((from, from + 1))
+    } else {
+        None
+    }
+}
+
+impl Diagnostic {
+    fn annotations(&self) -> Vec<Annotation<'_>> {
+        let primary = Annotation {
+            message: self.label.as_deref(),
+            primary: true,
+            span: &self.span,
+        };
+
+        #[rustfmt::skip]
+        let labels = self.labels.iter().map(|label| Annotation {
+            message: Some(&label.message),
+            primary: false,
+            span: &label.span,
+        });
+
+        std::iter::once(primary).chain(labels).collect()
+    }
+
+    /// Groups the annotations by source, the source of the primary span first.
+    fn groups(&self) -> Vec<Vec<Annotation<'_>>> {
+        let mut groups: Vec<Vec<Annotation>> = Vec::new();
+
+        for annotation in self.annotations() {
+            let source = &annotation.span.source;
+
+            #[rustfmt::skip]
+            match groups.iter_mut().find(|group| Rc::ptr_eq(&group[0].span.source, source)) {
+                Some(group) => group.push(annotation),
+                None => groups.push(vec![annotation]),
+            };
+        }
+
+        groups
+    }
+}
+
+/// Applies `style` to `text` when `color` is set.
+fn paint<F>(color: bool, text: &str, style: F) -> String
+where
+    F: Fn(ColoredString) -> ColoredString,
+{
+    if color {
+        style(text.normal()).to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+impl Diagnostic {
+    fn render(&self, f: &mut std::fmt::Formatter<'_>, color: bool) -> std::fmt::Result {
         #[rustfmt::skip]
         let kind =
         match self.kind {
-            Kind::Critical => "critical".red().on_black(),
-            Kind::Warning => "warning".yellow(),
-            Kind::Debug => "debug".green(),
-            Kind::Error => "error".red(),
+            Kind::Critical => paint(color, "critical", |text| text.red().on_black().bold()),
+            Kind::Warning => paint(color, "warning", |text| text.yellow().bold()),
+            Kind::Debug => paint(color, "debug", |text| text.green().bold()),
+            Kind::Error => paint(color, "error", |text| text.red().bold()),
         };
 
+        let blue = |text: &str| paint(color, text, |text| text.blue().bold());
+        let bold = |text: &str| paint(color, text, |text| text.bold());
+
+        let groups = self.groups();
+
         #[rustfmt::skip]
-        let source_line =
-        if let Ok(file) = std::fs::File::open(&self.span.source.as_ref()) {
-            let reader = std::io::BufReader::new(file);
-            if let Some(line) = reader.lines().nth(self.span.start.row) {
-                line.unwrap_or_default()
-            } else {
-                String::default()
-            }
-        } else {
-            String::default()
-        };
+        let width = groups.iter().flatten().map(|annotation| last_row(annotation.span) + 1).max().unwrap_or(1).to_string().len();
 
-        let mut arrow_line = String::new();
+        let gutter = |number: &str| blue(&format!("{:>width$} |", number));
 
-        let space_padding = " ".repeat(self.span.start.col);
-        let arrow_padding = "^".repeat(self.span.end.abs - self.span.start.abs);
+        write!(f, "{}{} {}", kind, bold(":"), bold(&self.message))?;
 
-        arrow_line.push_str(&space_padding);
-        arrow_line.push_str(&arrow_padding);
+        for (index, group) in groups.iter().enumerate() {
+            let span = group[0].span;
+            let arrow = if index == 0 { "-->" } else { ":::" };
 
-        let location_info = format!(
-            "{}:{}:{}",
-            self.span.source,
-            self.span.start.row + 1,
-            self.span.start.col + 1
-        );
+            write!(
+                f,
+                "\n{}{} {}:{}:{}\n{}",
+                " ".repeat(width),
+                blue(arrow),
+                span.source.name,
+                span.start.row + 1,
+                span.start.col + 1,
+                gutter("")
+            )?;
 
-        // The following looks horrible...
-        let line_info = format!(
-            "{:<3} | {}",
-            self.span.start.row.to_string().blue().bold(),
-            source_line
-        );
+            let mut rows: Vec<usize> = group
+                .iter()
+                .flat_map(|annotation| rows(annotation.span))
+                .collect();
+            rows.sort_unstable();
+            rows.dedup();
 
-        let formatted_message = format!(
-            "{}{} {}\n   {} {}\n    |\n{}\n    | {}",
-            kind.bold(),
-            ":".bold(),
-            self.message.bold(),
-            "-->".blue().bold(),
-            location_info,
-            line_info,
-            arrow_line.yellow().bold()
-        );
+            let mut previous: Option<usize> = None;
+
+            for row in rows {
+                if previous.is_some_and(|previous| row > previous + 1) {
+                    write!(f, "\n{}", blue("..."))?;
+                }
+
+                previous = Some(row);
+
+                let line = span.source.line(row).unwrap_or_default().replace('\t', " ");
+                let length = line.chars().count();
+
+                write!(f, "\n{} {}", gutter(&(row + 1).to_string()), line)?;
 
-        let message = formatted_message
-            .replace("|", &"|".blue().bold().to_string())
-            .replace("-->", &"-->".blue().bold().to_string());
+                for annotation in group {
+                    let Some((from, to)) = columns(annotation.span, row, length) else {
+                        continue;
+                    };
 
-        write!(f, "{}", message)
+                    let marker = if annotation.primary { "^" } else { "-" };
+                    let mut underline = marker.repeat(to - from);
+
+                    if let Some(message) = annotation
+                        .message
+                        .filter(|_| row == last_row(annotation.span))
+                    {
+                        underline.push(' ');
+                        underline.push_str(message);
+                    }
+
+                    #[rustfmt::skip]
+                    let underline =
+                    if annotation.primary {
+                        paint(color, &underline, |text| text.yellow().bold())
+                    } else {
+                        blue(&underline)
+                    };
+
+                    write!(f, "\n{} {}{}", gutter(""), " ".repeat(from), underline)?;
+                }
+            }
+        }
+
+        for (name, messages) in [("note", &self.notes), ("help", &self.help)] {
+            for message in messages {
+                write!(
+                    f,
+                    "\n{} {} {}{} {}",
+                    " ".repeat(width),
+                    blue("="),
+                    bold(name),
+                    bold(":"),
+                    message
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renders the diagnostic without colors, e.g. for logs.
+    pub fn plain(&self) -> Plain<'_> {
+        Plain(self)
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.render(f, true)
+    }
+}
+
+/// The text rendering of a [`Diagnostic`] without terminal colors.
+pub struct Plain<'a>(&'a Diagnostic);
+
+impl std::fmt::Display for Plain<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.render(f, false)
     }
 }
 
 impl std::error::Error for Diagnostic {}
 
 impl Diagnostic {
-    #[rustfmt::skip]
     pub fn new(kind: Kind, message: &str, span: Span) -> Self {
-        Self { kind, message: message.to_string(), span }
+        Self {
+            help: Vec::new(),
+            kind,
+            label: None,
+            labels: Vec::new(),
+            message: message.to_string(),
+            notes: Vec::new(),
+            span,
+        }
+    }
+
+    /// Adds a `help:` trailer suggesting how to fix the problem.
+    pub fn with_help(mut self, message: &str) -> Self {
+        self.help.push(message.to_string());
+        self
+    }
+
+    /// Adds a secondary label, drawn with `-` under its span.
+    pub fn with_label(mut self, span: Span, message: &str) -> Self {
+        self.labels.push(Label::new(span, message));
+        self
+    }
+
+    /// Adds a `note:` trailer giving background on the problem.
+    pub fn with_note(mut self, message: &str) -> Self {
+        self.notes.push(message.to_string());
+        self
+    }
+
+    /// Sets the message drawn next to the `^` under the primary span.
+    pub fn with_primary_label(mut self, message: &str) -> Self {
+        self.label = Some(message.to_string());
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analyzer::source::Source;
+
+    use super::{Diagnostic, Kind, Position, Span};
+
+    #[test]
+    fn test() {
+        let source = Source::string("test.mt", "div {\n  p\n}\nspan <-");
+
+        #[rustfmt::skip]
+        let position = |abs, row, col| Position { abs, byte: abs, col, row };
+
+        let block = Span::new(source.clone(), position(4, 0, 4), position(11, 2, 1));
+        let borrow = Span::new(source.clone(), position(12, 3, 0), position(19, 3, 7));
+
+        let diagnostic = Diagnostic::new(Kind::Error, "message", borrow)
+            .with_primary_label("here")
+            .with_label(block, "block")
+            .with_note("a note")
+            .with_help("some help");
+
+        assert_eq!(
+            diagnostic.plain().to_string(),
+            "error: message\n --> test.mt:4:1\n  |\n1 | div {\n  |     -\n2 |   p\n  | ---\n3 | }\n  | - block\n4 | span <-\n  | ^^^^^^^ here\n  = note: a note\n  = help: some help"
+        );
     }
 }
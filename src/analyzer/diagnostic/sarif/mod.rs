@@ -0,0 +1,150 @@
+use serde_json::{json, Value};
+
+use crate::analyzer::diagnostic::{Diagnostic, Kind, Span};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+fn level(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Critical | Kind::Error => "error",
+        Kind::Warning => "warning",
+        Kind::Debug => "note",
+    }
+}
+
+/// The `physicalLocation` of a span; columns count code points, as declared
+/// by the run. A source with no URI, such as the standard input, is only
+/// described by its name.
+fn location(span: &Span) -> Value {
+    #[rustfmt::skip]
+    let artifact = match span.source.uri() {
+        Some(uri) => json!({ "uri": uri }),
+        None => json!({ "description": { "text": span.source.name } }),
+    };
+
+    json!({
+        "physicalLocation": {
+            "artifactLocation": artifact,
+            "region": {
+                "byteLength": span.end.byte.saturating_sub(span.start.byte),
+                "byteOffset": span.start.byte,
+                "charLength": span.end.abs.saturating_sub(span.start.abs),
+                "charOffset": span.start.abs,
+                "endColumn": span.end.col + 1,
+                "endLine": span.end.row + 1,
+                "startColumn": span.start.col + 1,
+                "startLine": span.start.row + 1,
+            },
+        },
+    })
+}
+
+fn result(diagnostic: &Diagnostic) -> Value {
+    let mut text = diagnostic.message.clone();
+
+    for note in &diagnostic.notes {
+        text.push_str(&format!("\nnote: {}", note));
+    }
+
+    for help in &diagnostic.help {
+        text.push_str(&format!("\nhelp: {}", help));
+    }
+
+    #[rustfmt::skip]
+    let related: Vec<Value> = diagnostic.labels.iter().enumerate().map(|(id, label)| {
+        let mut location = location(&label.span);
+        location["id"] = json!(id);
+        location["message"] = json!({ "text": label.message });
+
+        location
+    }).collect();
+
+    let mut location = location(&diagnostic.span);
+
+    if let Some(label) = &diagnostic.label {
+        location["message"] = json!({ "text": label });
+    }
+
+    json!({
+        "level": level(diagnostic.kind),
+        "locations": [location],
+        "message": { "text": text },
+        "relatedLocations": related,
+    })
+}
+
+/// Builds a SARIF 2.1.0 log holding one run with every diagnostic.
+pub fn log<'a, I>(diagnostics: I) -> Value
+where
+    I: IntoIterator<Item = &'a Diagnostic>,
+{
+    let results: Vec<Value> = diagnostics.into_iter().map(result).collect();
+
+    json!({
+        "$schema": SCHEMA,
+        "runs": [{
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+            "tool": {
+                "driver": {
+                    "informationUri": "https://github.com/FlamesX-128/Markup-Type",
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            },
+        }],
+        "version": "2.1.0",
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value;
+
+    use crate::analyzer::{
+        diagnostic::{Diagnostic, Kind, Position, Span},
+        source::{self, Source},
+    };
+
+    #[test]
+    fn test() {
+        let source = Source::string("test.mt", "é <-");
+
+        #[rustfmt::skip]
+        let span = Span::new(source, Position { abs: 0, byte: 0, col: 0, row: 0 }, Position { abs: 4, byte: 5, col: 4, row: 0 });
+
+        let diagnostic = Diagnostic::new(Kind::Warning, "message", span).with_note("a note");
+
+        let log = super::log([&diagnostic]);
+        let result = &log["runs"][0]["results"][0];
+
+        assert_eq!(log["version"], "2.1.0");
+        assert_eq!(result["level"], "warning");
+        assert_eq!(result["message"]["text"], "message\nnote: a note");
+
+        let region = &result["locations"][0]["physicalLocation"]["region"];
+
+        assert_eq!(region["byteLength"], 5);
+        assert_eq!(region["endColumn"], 5);
+
+        let artifact = &result["locations"][0]["physicalLocation"]["artifactLocation"];
+
+        assert_eq!(artifact["uri"], Value::Null);
+        assert_eq!(artifact["description"]["text"], "test.mt");
+
+        let file = Source::new(source::Kind::File, "/site/my page%.mt", "p");
+        let span = Span::new(file, Position::default(), Position::default());
+
+        let log = super::log([&Diagnostic::new(Kind::Error, "message", span)]);
+        let artifact =
+            &log["runs"][0]["results"][0]["locations"][0]["physicalLocation"]["artifactLocation"];
+
+        assert_eq!(artifact["uri"], "file:///site/my%20page%25.mt");
+
+        let line = serde_json::to_value(&diagnostic).unwrap_or_default();
+
+        assert_eq!(line["kind"], "warning");
+        assert_eq!(line["span"]["file"], "test.mt");
+        assert_eq!(line["span"]["end"]["byte"], 5);
+    }
+}
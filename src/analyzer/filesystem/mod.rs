@@ -1,30 +1,280 @@
-use std::io::Read;
+use std::io::{BufRead, ErrorKind, Read};
 
-pub struct FileReader {
-    reader: std::io::BufReader<std::fs::File>,
+use crate::analyzer::diagnostic::Position;
+
+#[derive(Clone, Debug)]
+pub struct InvalidSequence {
+    pub bytes: Vec<u8>,
+    pub end: Position,
+    pub start: Position,
+}
+
+impl std::fmt::Display for InvalidSequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bytes: Vec<String> = self
+            .bytes
+            .iter()
+            .map(|byte| format!("{:#04x}", byte))
+            .collect();
+
+        write!(f, "invalid UTF-8 sequence [{}]", bytes.join(", "))
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Utf8(InvalidSequence),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{}", error),
+            Error::Utf8(sequence) => write!(f, "{}", sequence),
+        }
+    }
 }
 
-impl Iterator for FileReader {
-    type Item = char;
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Decodes a file (or any other reader) as UTF-8, one character at a time.
+///
+/// Every invalid byte sequence is reported as an [`Error::Utf8`] that takes the
+/// place of a single character, so reading can continue after it. A reader
+/// with a limit fails with an [`Error::Io`] instead of reading past it.
+pub struct FileReader<R = std::fs::File>
+where
+    R: Read,
+{
+    finished: bool,
+    /// The most bytes that can be read.
+    limit: Option<usize>,
+    position: Position,
+    read: usize,
+    reader: std::io::BufReader<R>,
+}
+
+impl<R> FileReader<R>
+where
+    R: Read,
+{
+    fn peek_byte(&mut self) -> std::io::Result<Option<u8>> {
+        loop {
+            match self.reader.fill_buf() {
+                Ok(buffer) => return Ok(buffer.first().copied()),
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    fn consume(&mut self) -> std::io::Result<()> {
+        if let Some(limit) = self.limit.filter(|limit| self.read >= *limit) {
+            let message = format!("the input is longer than the limit of {} bytes", limit);
+
+            return Err(std::io::Error::new(ErrorKind::InvalidData, message));
+        }
+
+        self.reader.consume(1);
+        self.read += 1;
+
+        Ok(())
+    }
+
+    fn next_byte(&mut self) -> std::io::Result<Option<u8>> {
+        let byte = self.peek_byte()?;
+
+        if byte.is_some() {
+            self.consume()?;
+        }
+
+        Ok(byte)
+    }
+
+    fn advance(&mut self, char: Option<char>) -> (Position, Position) {
+        let start = self.position;
+
+        self.position.abs += 1;
+        self.position.byte += char.unwrap_or(char::REPLACEMENT_CHARACTER).len_utf8();
+
+        if char == Some('\n') {
+            self.position.row += 1;
+            self.position.col = 0;
+        } else {
+            self.position.col += 1;
+        }
+
+        (start, self.position)
+    }
+
+    fn decode(&mut self, lead: u8) -> std::io::Result<Result<char, Vec<u8>>> {
+        #[rustfmt::skip]
+        let length = match lead {
+            0x00..=0x7F => 1,
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => 0,
+        };
+
+        let mut bytes = vec![lead];
+
+        if length == 0 {
+            return Ok(Err(bytes));
+        }
+
+        while bytes.len() < length {
+            match self.peek_byte()? {
+                Some(byte @ 0x80..=0xBF) => {
+                    self.consume()?;
+                    bytes.push(byte);
+                }
+                _ => return Ok(Err(bytes)),
+            }
+        }
+
+        match std::str::from_utf8(&bytes) {
+            Ok(decoded) => Ok(decoded.chars().next().ok_or(bytes)),
+            Err(_) => Ok(Err(bytes)),
+        }
+    }
+}
+
+impl<R> Iterator for FileReader<R>
+where
+    R: Read,
+{
+    type Item = Result<char, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut buffer = [0; 1];
+        if self.finished {
+            return None;
+        }
+
+        let decoded = self.next_byte().and_then(|lead| match lead {
+            Some(lead) => self.decode(lead).map(Some),
+            None => Ok(None),
+        });
 
-        match self.reader.read(&mut buffer) {
-            Err(_) => {
-                unreachable!()
+        match decoded {
+            Ok(Some(Ok(char))) => {
+                self.advance(Some(char));
+
+                Some(Ok(char))
+            }
+            Ok(Some(Err(bytes))) => {
+                let (start, end) = self.advance(None);
+
+                Some(Err(Error::Utf8(InvalidSequence { bytes, end, start })))
+            }
+            Ok(None) => {
+                self.finished = true;
+
+                None
             }
-            Ok(0) => None,
-            Ok(_) => Some(buffer[0] as char),
+            Err(error) => {
+                self.finished = true;
+
+                Some(Err(Error::Io(error)))
+            }
+        }
+    }
+}
+
+impl<R> FileReader<R>
+where
+    R: Read,
+{
+    pub fn from_reader(reader: R) -> Self {
+        Self {
+            finished: false,
+            limit: None,
+            position: Position::default(),
+            read: 0,
+            reader: std::io::BufReader::new(reader),
         }
     }
+
+    /// Stops reading with an error once more than `limit` bytes are read.
+    pub fn with_limit(mut self, limit: Option<usize>) -> Self {
+        self.limit = limit;
+        self
+    }
 }
 
 impl FileReader {
     pub fn new(path: &str) -> std::io::Result<Self> {
         let file = std::fs::File::open(path)?;
-        let reader = std::io::BufReader::new(file);
 
-        Ok(Self { reader })
+        Ok(Self::from_reader(file))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use super::{Error, FileReader};
+
+    /// Hands out one byte per read, so every character is split across reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            let Some((&byte, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+
+            buffer[0] = byte;
+            self.0 = rest;
+
+            Ok(1)
+        }
+    }
+
+    fn describe(item: Result<char, Error>) -> String {
+        match item {
+            Ok(char) => char.to_string(),
+            Err(Error::Utf8(sequence)) => format!("{} at {}", sequence, sequence.start.byte),
+            Err(Error::Io(error)) => format!("io: {}", error),
+        }
+    }
+
+    #[test]
+    fn test() {
+        let bytes = b"a\xe0\x80\x80b\xed\xa0\x80\xc3\xa9\xf0\x9f\x98\x80\xe2\x82";
+        let items: Vec<String> = FileReader::from_reader(Trickle(bytes))
+            .map(describe)
+            .collect();
+
+        assert_eq!(
+            items,
+            [
+                "a",
+                "invalid UTF-8 sequence [0xe0, 0x80, 0x80] at 1",
+                "b",
+                "invalid UTF-8 sequence [0xed, 0xa0, 0x80] at 5",
+                "\u{e9}",
+                "\u{1f600}",
+                "invalid UTF-8 sequence [0xe2, 0x82] at 14",
+            ]
+        );
+
+        let items: Vec<String> = FileReader::from_reader(Trickle("a\u{e9}b".as_bytes()))
+            .with_limit(Some(2))
+            .map(describe)
+            .collect();
+
+        assert_eq!(
+            items,
+            ["a", "io: the input is longer than the limit of 2 bytes"]
+        );
     }
 }
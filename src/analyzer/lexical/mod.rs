@@ -1,6 +1,6 @@
 pub mod tokenizer;
 
-use crate::analyzer::diagnostic::Span;
+use crate::analyzer::diagnostic::{Diagnostic, Span};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Delimiter {
@@ -9,11 +9,15 @@ pub enum Delimiter {
     Semicolon,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Lexeme {
+    Boolean(bool),
     Comment(String),
+    Float(f64),
     Identifier(String),
+    Integer(i64),
     Literal(String),
+    Null,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -23,11 +27,19 @@ pub enum Operator {
     Pipe,
 }
 
+/// Text without meaning of its own, only produced by a lossless tokenizer.
 #[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Trivia {
+    Newline(String),
+    Whitespace(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Kind {
     Delimiter(Delimiter),
     Lexeme(Lexeme),
     Operator(Operator),
+    Trivia(Trivia),
     Unknown(String),
 }
 
@@ -35,10 +47,16 @@ pub enum Kind {
 pub struct Token {
     pub kind: Kind,
     pub span: Span,
+    /// The problems found while reading the token, such as invalid escapes.
+    pub trace: Vec<Diagnostic>,
 }
 
 impl Token {
     pub fn new(kind: Kind, span: Span) -> Self {
-        Self { kind, span }
+        Self {
+            kind,
+            span,
+            trace: Vec::new(),
+        }
     }
 }
@@ -1,29 +1,73 @@
-use std::{iter::Peekable, rc::Rc};
+use std::{collections::VecDeque, iter::Peekable, rc::Rc};
 
 use iterator_stage::ConfigurableProcessor;
 
-use crate::analyzer::diagnostic::{Position, Span};
+use crate::analyzer::{
+    diagnostic::{self, Diagnostic, Position, Span},
+    source::Source,
+};
 
-use super::{Delimiter, Kind, Lexeme, Operator, Token};
+use super::{Delimiter, Kind, Lexeme, Operator, Token, Trivia};
 
 pub struct Analyzer<T>
 where
     T: Iterator<Item = char>,
 {
+    finished: bool,
+    /// The most bytes a token can have.
+    limit: usize,
+    /// The characters peeked past the next one, read before the reader.
+    lookahead: VecDeque<char>,
+    lossless: bool,
     reader: Peekable<T>,
     position: Position,
-    source: Rc<String>,
+    source: Rc<Source>,
+    /// Where the token being read starts.
+    start: Position,
+    trace: Vec<Diagnostic>,
 }
 
 impl<T> Analyzer<T>
 where
     T: Iterator<Item = char>,
 {
+    /// Whether the token being read is longer than the limit, in which case
+    /// the input seems to end right there.
+    fn is_over_limit(&self) -> bool {
+        self.position.byte - self.start.byte > self.limit
+    }
+
+    fn peek(&mut self) -> Option<&char> {
+        if self.is_over_limit() {
+            return None;
+        }
+
+        match self.lookahead.front() {
+            Some(char) => Some(char),
+            None => self.reader.peek(),
+        }
+    }
+
+    /// Peeks the character `offset` places ahead, where an offset of 0 is the
+    /// next character, the one [`Self::peek`] returns.
+    fn peek_nth(&mut self, offset: usize) -> Option<char> {
+        while self.lookahead.len() <= offset {
+            self.lookahead.push_back(self.reader.next()?);
+        }
+
+        self.lookahead.get(offset).copied()
+    }
+
     fn advance(&mut self) -> Option<char> {
-        let item = self.reader.next();
+        if self.is_over_limit() {
+            return None;
+        }
+
+        let item = self.lookahead.pop_front().or_else(|| self.reader.next());
 
         if let Some(char) = item {
             self.position.abs += 1;
+            self.position.byte += char.len_utf8();
 
             if char == '\n' {
                 self.position.row += 1;
@@ -35,15 +79,23 @@ where
 
         item
     }
+
+    /// Reports a problem in the token being read, from `start` to here.
+    fn report(&mut self, message: &str, start: Position) {
+        let span = Span::new(self.source.clone(), start, self.position);
+        let diagnostic = Diagnostic::new(diagnostic::Kind::Error, message, span);
+
+        self.trace.push(diagnostic);
+    }
 }
 
 impl<T> Analyzer<T>
 where
     T: Iterator<Item = char>,
 {
-    fn tokenize_delimiter(&mut self) -> Token {
+    fn tokenize_delimiter(&mut self, char: char) -> Token {
         let position = self.position.clone();
-        let char = self.advance().unwrap();
+        self.advance();
 
         #[rustfmt::skip]
         let kind = match char {
@@ -57,7 +109,7 @@ where
                 Kind::Delimiter(Delimiter::Semicolon)
             },
             _ => {
-                unreachable!();
+                Kind::Unknown(char.to_string())
             }
         };
 
@@ -72,10 +124,65 @@ impl<T> Analyzer<T>
 where
     T: Iterator<Item = char>,
 {
-    fn tokenize_comment_lexeme(&mut self) -> Kind {
-        let mut content = String::new();
+    /// Whether the next characters are `*` and a space or line break, which
+    /// opens a block comment after a `#`. A line such as `#****` stays a line
+    /// comment, as it was before block comments existed.
+    fn is_block_comment(&mut self) -> bool {
+        self.peek_nth(0) == Some('*') && matches!(self.peek_nth(1), Some(' ' | '\t' | '\n' | '\r'))
+    }
+
+    /// Reads `#* ... *#` block comments, which can span lines and nest, so a
+    /// whole subtree holding comments of its own can be commented out.
+    fn tokenize_block_comment_lexeme(&mut self, start: Position, mut content: String) -> Kind {
+        if let Some(char) = self.advance() {
+            content.push(char);
+        }
+
+        let opening = Span::new(self.source.clone(), start, self.position);
+
+        let mut depth = 1_usize;
+        let mut previous = None;
 
         while let Some(char) = self.advance() {
+            content.push(char);
+
+            #[rustfmt::skip]
+            match (previous, char) {
+                (Some('#'), '*') if matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) => {
+                    depth += 1;
+                    previous = None;
+                },
+                (Some('*'), '#') => {
+                    depth -= 1;
+                    previous = None;
+
+                    if depth == 0 {
+                        return Kind::Lexeme(Lexeme::Comment(content));
+                    }
+                },
+                _ => {
+                    previous = Some(char);
+                }
+            };
+        }
+
+        self.report_unclosed("comment", opening, &"*#".repeat(depth));
+
+        Kind::Lexeme(Lexeme::Comment(content))
+    }
+
+    fn tokenize_comment_lexeme(&mut self, start: Position) -> Kind {
+        let mut content = String::new();
+
+        if let Some(char) = self.advance() {
+            content.push(char);
+        }
+
+        if self.is_block_comment() {
+            return self.tokenize_block_comment_lexeme(start, content);
+        }
+
+        while let Some(&char) = self.peek() {
             #[rustfmt::skip]
             match char {
                 | '\n'
@@ -86,6 +193,8 @@ where
                     content.push(char);
                 }
             };
+
+            self.advance();
         }
 
         let lexeme = Lexeme::Comment(content);
@@ -94,10 +203,10 @@ where
         kind
     }
 
-    fn tokenize_identifier_lexeme(&mut self) -> Kind {
+    fn tokenize_identifier_lexeme(&mut self, start: Position) -> Kind {
         let mut content = String::new();
 
-        while let Some(&char) = self.reader.peek() {
+        while let Some(&char) = self.peek() {
             #[rustfmt::skip]
             match char {
                 | '0'..='9'
@@ -107,6 +216,14 @@ where
                 | '_' => {
                     content.push(char);
                 },
+                // The shorthand of `div.card#main` only takes a `#` a name
+                // follows, so `div# note` is still an element and a comment.
+                '.' => {
+                    content.push(char);
+                },
+                '#' if self.peek_nth(1).is_some_and(|next| next.is_ascii_alphabetic() || matches!(next, '-' | '_')) => {
+                    content.push(char);
+                },
                 _ => {
                     break;
                 }
@@ -115,58 +232,379 @@ where
             self.advance();
         }
 
-        let lexeme = Lexeme::Identifier(content);
+        if content == "r" && self.is_raw_literal() {
+            return self.tokenize_raw_literal_lexeme(start);
+        }
+
+        #[rustfmt::skip]
+        let lexeme = match content.as_str() {
+            "false" => Lexeme::Boolean(false),
+            "null" => Lexeme::Null,
+            "true" => Lexeme::Boolean(true),
+            _ => Lexeme::Identifier(content),
+        };
+
         let kind = Kind::Lexeme(lexeme);
 
         kind
     }
 
-    fn tokenize_literal_lexeme(&mut self) -> Kind {
-        let delimiter = self.advance().unwrap();
+    fn tokenize_digits(&mut self, content: &mut String) {
+        while let Some(&char) = self.peek().filter(|char| char.is_ascii_digit()) {
+            content.push(char);
+            self.advance();
+        }
+    }
+
+    /// Reads integers such as `-12` and floats such as `1.5e-3`, where the
+    /// sign, if any, is already in `content`. A number that cannot be read is
+    /// reported and kept as a literal with the text it was written with.
+    fn tokenize_number_lexeme(&mut self, start: Position, mut content: String) -> Kind {
+        let mut float = false;
+
+        self.tokenize_digits(&mut content);
+
+        if self.peek() == Some(&'.') {
+            float = true;
+
+            content.extend(self.advance());
+            self.tokenize_digits(&mut content);
+        }
+
+        if let Some(&char @ ('e' | 'E')) = self.peek() {
+            float = true;
+
+            content.push(char);
+            self.advance();
+
+            if let Some(&sign @ ('+' | '-')) = self.peek() {
+                content.push(sign);
+                self.advance();
+            }
+
+            self.tokenize_digits(&mut content);
+        }
+
+        let mut suffix = String::new();
+
+        while let Some(&char) = self.peek() {
+            if !(char.is_alphanumeric() || matches!(char, '-' | '_' | '.')) {
+                break;
+            }
+
+            suffix.push(char);
+            self.advance();
+        }
+
+        #[rustfmt::skip]
+        let lexeme = match (float, suffix.is_empty()) {
+            (_, false) => Err(format!("a number cannot be followed by '{}', write \"{}{}\" to have text", suffix, content, suffix)),
+            (true, true) => match content.parse::<f64>() {
+                Ok(float) if float.is_finite() => Ok(Lexeme::Float(float)),
+                Ok(_) => Err(format!("the number {} does not fit in a 64-bit float", content)),
+                Err(_) => Err(format!("'{}' is not a valid number", content)),
+            },
+            (false, true) => content.parse().map(Lexeme::Integer).map_err(|_| format!("the integer {} does not fit in 64 bits", content)),
+        };
+
+        match lexeme {
+            Ok(lexeme) => Kind::Lexeme(lexeme),
+            Err(message) => {
+                self.report(&message, start);
+
+                Kind::Lexeme(Lexeme::Literal(content + &suffix))
+            }
+        }
+    }
+
+    fn tokenize_unicode_escape(&mut self, start: Position) -> Option<char> {
+        if self.peek() != Some(&'{') {
+            self.report("a unicode escape is written as `\\u{...}`", start);
+
+            return None;
+        }
+
+        self.advance();
+
+        let mut digits = String::new();
+
+        while let Some(&char) = self.peek() {
+            if !char.is_ascii_hexdigit() {
+                break;
+            }
+
+            digits.push(char);
+            self.advance();
+        }
+
+        if self.peek() != Some(&'}') {
+            let message = "a unicode escape only has hex digits and ends with `}`";
+
+            self.report(message, start);
+
+            return None;
+        }
+
+        self.advance();
+
+        if digits.is_empty() || digits.len() > 6 {
+            self.report("a unicode escape has from 1 to 6 hex digits", start);
+
+            return None;
+        }
+
+        let char = u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32);
+
+        if char.is_none() {
+            let message = format!("'{}' is not a unicode character", digits);
+
+            self.report(&message, start);
+        }
+
+        char
+    }
+
+    fn tokenize_escape(&mut self, start: Position, content: &mut String) {
+        let Some(char) = self.advance() else {
+            return;
+        };
+
+        #[rustfmt::skip]
+        let escaped = match char {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            '\\' | '"' | '\'' => char,
+            'u' => {
+                content.extend(self.tokenize_unicode_escape(start));
+
+                return;
+            },
+            _ => {
+                let message = format!("unknown escape sequence `\\{}`", char);
+
+                self.report(&message, start);
+
+                content.push('\\');
+                char
+            }
+        };
+
+        content.push(escaped);
+    }
+
+    /// Reports a literal or block comment that reaches the end of the input,
+    /// at its opening delimiter.
+    fn report_unclosed(&mut self, what: &str, opening: Span, closing: &str) {
+        let end = Span::new(self.source.clone(), self.position, self.position);
+
+        let message = format!("this {} is never closed", what);
+
+        let diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, opening)
+            .with_primary_label(&format!("the {} starts here", what))
+            .with_label(end, "the input ends here")
+            .with_help(&format!("close the {} with `{}`", what, closing));
+
+        self.trace.push(diagnostic);
+    }
+
+    /// Reads `"""` block literals. Lines holding only the delimiters are left
+    /// out, and so is the indentation every other line shares, so the block
+    /// can be indented along with the element it belongs to.
+    fn tokenize_block_literal_lexeme(&mut self, delimiter: char, start: Position) -> Kind {
+        let quote = Span::new(self.source.clone(), start, self.position);
+
+        let mut lines: Vec<(String, String)> = vec![Default::default()];
+        let mut closed = false;
+
+        while let Some(&char) = self.peek() {
+            let position = self.position;
+            self.advance();
+
+            let Some((indentation, text)) = lines.last_mut() else {
+                break;
+            };
+
+            match char {
+                '\\' => self.tokenize_escape(position, text),
+                '\r' if self.peek() == Some(&'\n') => {}
+                '\n' => lines.push(Default::default()),
+                '\t' | ' ' if text.is_empty() => indentation.push(char),
+                _ if char == delimiter => {
+                    let mut count = 1;
+
+                    while count < 3 && self.peek() == Some(&delimiter) {
+                        self.advance();
+                        count += 1;
+                    }
+
+                    if count == 3 {
+                        closed = true;
+
+                        break;
+                    }
+
+                    text.extend(std::iter::repeat_n(delimiter, count));
+                }
+                _ => text.push(char),
+            }
+        }
+
+        if !closed {
+            self.report_unclosed("literal", quote, &delimiter.to_string().repeat(3));
+        }
+
+        if lines.len() > 1 && lines.first().is_some_and(|(_, text)| text.is_empty()) {
+            lines.remove(0);
+        }
+
+        if lines.len() > 1 && lines.last().is_some_and(|(_, text)| text.is_empty()) {
+            lines.pop();
+        }
+
+        let width = lines
+            .iter()
+            .filter(|(_, text)| !text.is_empty())
+            .map(|(indentation, _)| indentation.chars().count())
+            .min()
+            .unwrap_or(0);
+
+        #[rustfmt::skip]
+        let lines: Vec<String> = lines.into_iter().map(|(indentation, text)| {
+            if text.is_empty() {
+                return text;
+            }
+
+            indentation.chars().skip(width).chain(text.chars()).collect()
+        }).collect();
+
+        let lexeme = Lexeme::Literal(lines.join("\n"));
+        let kind = Kind::Lexeme(lexeme);
+
+        kind
+    }
+
+    fn tokenize_literal_lexeme(&mut self, delimiter: char) -> Kind {
+        let start = self.position;
+        self.advance();
+
+        if self.peek() == Some(&delimiter) {
+            self.advance();
+
+            if self.peek() == Some(&delimiter) {
+                self.advance();
+
+                return self.tokenize_block_literal_lexeme(delimiter, start);
+            }
+
+            return Kind::Lexeme(Lexeme::Literal(String::new()));
+        }
+
+        let quote = Span::new(self.source.clone(), start, self.position);
 
         let mut content = String::new();
-        let mut escape = false;
+        let mut closed = false;
 
-        while let Some(char) = self.advance() {
-            if escape {
-                content.push(char);
-                escape = false;
-            } else if char == '\\' {
-                escape = true;
+        while let Some(&char) = self.peek() {
+            let position = self.position;
+            self.advance();
+
+            if char == '\\' {
+                self.tokenize_escape(position, &mut content);
             } else if char == delimiter {
+                closed = true;
+
                 break;
             } else {
                 content.push(char);
             }
         }
 
+        if !closed {
+            self.report_unclosed("literal", quote, &delimiter.to_string());
+        }
+
         let lexeme = Lexeme::Literal(content);
         let kind = Kind::Lexeme(lexeme);
 
         kind
     }
 
-    fn tokenize_lexeme(&mut self) -> Token {
+    /// Whether the `r` just read starts a raw literal: the next characters are
+    /// any number of `#` and then a quote, rather than a comment after an `r`.
+    fn is_raw_literal(&mut self) -> bool {
+        let mut offset = 0;
+
+        while self.peek_nth(offset) == Some('#') {
+            offset += 1;
+        }
+
+        matches!(self.peek_nth(offset), Some('"' | '\''))
+    }
+
+    /// Reads `r"..."` raw literals, where nothing is escaped; any number of
+    /// `#` can follow the `r` and must then follow the closing quote too.
+    fn tokenize_raw_literal_lexeme(&mut self, start: Position) -> Kind {
+        let mut hashes = String::new();
+
+        while self.peek() == Some(&'#') {
+            hashes.push('#');
+            self.advance();
+        }
+
+        let delimiter = match self.peek() {
+            Some(&char @ ('"' | '\'')) => char,
+            _ => return Kind::Unknown(format!("r{}", hashes)),
+        };
+
+        self.advance();
+
+        let quote = Span::new(self.source.clone(), start, self.position);
+        let closing = format!("{}{}", delimiter, hashes);
+
+        let mut content = String::new();
+
+        while let Some(char) = self.advance() {
+            content.push(char);
+
+            if content.ends_with(&closing) {
+                content.truncate(content.len() - closing.len());
+
+                return Kind::Lexeme(Lexeme::Literal(content));
+            }
+        }
+
+        self.report_unclosed("literal", quote, &closing);
+
+        Kind::Lexeme(Lexeme::Literal(content))
+    }
+
+    fn tokenize_lexeme(&mut self, char: char) -> Token {
         let position = self.position.clone();
-        let char = self.reader.peek().unwrap();
 
         #[rustfmt::skip]
         let kind = match char {
             '#' => {
-                self.tokenize_comment_lexeme()
+                self.tokenize_comment_lexeme(position)
             },
             | 'A'..='Z'
             | 'a'..='z'
             | '-'
             | '_' => {
-                self.tokenize_identifier_lexeme()
+                self.tokenize_identifier_lexeme(position)
             },
             | '\''
             | '"' => {
-                self.tokenize_literal_lexeme()
+                self.tokenize_literal_lexeme(char)
+            },
+            '0'..='9' => {
+                self.tokenize_number_lexeme(position, String::new())
             },
             _ => {
-                unreachable!()
+                return self.tokenize_unknown();
             }
         };
 
@@ -184,7 +622,7 @@ where
     fn tokenize_borrow_operator(&mut self) -> Kind {
         let symbol = "<".into();
 
-        if let Some(&char) = self.reader.peek() {
+        if let Some(&char) = self.peek() {
             if char == '-' {
                 self.advance();
 
@@ -197,14 +635,16 @@ where
         }
     }
 
-    fn tokenize_borrowable_transfer_operator(&mut self) -> Kind {
+    fn tokenize_borrowable_transfer_operator(&mut self, start: Position) -> Kind {
         let symbol = "-".into();
 
-        if let Some(&char) = self.reader.peek() {
+        if let Some(&char) = self.peek() {
             if char == '>' {
                 self.advance();
 
                 Kind::Operator(Operator::Borrowable)
+            } else if char.is_ascii_digit() {
+                self.tokenize_number_lexeme(start, symbol)
             } else {
                 Kind::Unknown(symbol)
             }
@@ -217,9 +657,9 @@ where
         Kind::Operator(Operator::Pipe)
     }
 
-    fn tokenize_operator(&mut self) -> Token {
+    fn tokenize_operator(&mut self, char: char) -> Token {
         let position = self.position.clone();
-        let char = self.advance().unwrap();
+        self.advance();
 
         #[rustfmt::skip]
         let kind = match char {
@@ -227,13 +667,13 @@ where
                 self.tokenize_borrow_operator()
             },
             '-' => {
-                self.tokenize_borrowable_transfer_operator()
+                self.tokenize_borrowable_transfer_operator(position)
             }
             '|' => {
                 self.tokenize_pipe_operator()
             },
             _ => {
-                unreachable!()
+                Kind::Unknown(char.to_string())
             }
         };
 
@@ -253,7 +693,7 @@ where
 
         let mut content = String::new();
 
-        while let Some(&char) = self.reader.peek() {
+        while let Some(&char) = self.peek() {
             match char {
                 '\n' | '\r' | '\t' | ' ' => break,
                 _ => {
@@ -273,6 +713,48 @@ where
     }
 }
 
+impl<T> Analyzer<T>
+where
+    T: Iterator<Item = char>,
+{
+    fn tokenize_trivia(&mut self) -> Token {
+        let position = self.position;
+
+        let mut content = String::new();
+
+        #[rustfmt::skip]
+        let newline = match self.peek() {
+            Some('\n') => true,
+            Some('\r') => {
+                content.extend(self.advance());
+
+                self.peek() == Some(&'\n')
+            },
+            _ => false,
+        };
+
+        if newline {
+            content.extend(self.advance());
+        } else {
+            while let Some(&char) = self.peek() {
+                if !matches!(char, '\t' | ' ') {
+                    break;
+                }
+
+                content.push(char);
+                self.advance();
+            }
+        }
+
+        #[rustfmt::skip]
+        let trivia = if newline { Trivia::Newline(content) } else { Trivia::Whitespace(content) };
+
+        let span = Span::new(self.source.clone(), position, self.position);
+
+        Token::new(Kind::Trivia(trivia), span)
+    }
+}
+
 impl<T> Iterator for Analyzer<T>
 where
     T: Iterator<Item = char>,
@@ -280,24 +762,38 @@ where
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(char) = self.reader.peek() {
+        if self.finished {
+            return None;
+        }
+
+        loop {
+            self.start = self.position;
+
+            let &char = self.peek()?;
+
             let token = match char {
+                '\n' | '\r' | '\t' | ' ' if self.lossless => {
+                    let token = self.tokenize_trivia();
+
+                    Some(token)
+                }
                 '\n' | '\r' | '\t' | ' ' => {
                     self.advance();
-                    self.next()
+
+                    continue;
                 }
                 '{' | '}' | ';' => {
-                    let token = self.tokenize_delimiter();
+                    let token = self.tokenize_delimiter(char);
 
                     Some(token)
                 }
-                'A'..='Z' | 'a'..='z' | '\'' | '"' | '#' | '_' => {
-                    let token = self.tokenize_lexeme();
+                'A'..='Z' | 'a'..='z' | '0'..='9' | '\'' | '"' | '#' | '_' => {
+                    let token = self.tokenize_lexeme(char);
 
                     Some(token)
                 }
                 '<' | '-' | '|' => {
-                    let token = self.tokenize_operator();
+                    let token = self.tokenize_operator(char);
 
                     Some(token)
                 }
@@ -308,9 +804,26 @@ where
                 }
             };
 
-            token
-        } else {
-            None
+            return token.map(|mut token| {
+                token.trace = std::mem::take(&mut self.trace);
+
+                if self.is_over_limit() {
+                    let message = format!(
+                        "this token is longer than the limit of {} bytes",
+                        self.limit
+                    );
+
+                    let diagnostic =
+                        Diagnostic::new(diagnostic::Kind::Critical, &message, token.span.clone())
+                            .with_note("the analysis stopped here");
+
+                    // What the token would have been is only a guess past the limit.
+                    token.trace = vec![diagnostic];
+                    self.finished = true;
+                }
+
+                token
+            });
         }
     }
 }
@@ -319,21 +832,40 @@ impl<T> Analyzer<T>
 where
     T: Iterator<Item = char>,
 {
-    pub fn new<Y>(source: Y, reader: T) -> Self
-    where
-        Y: AsRef<str>,
-    {
+    pub fn new(source: Rc<Source>, reader: T) -> Self {
         Self {
+            finished: false,
+            limit: usize::MAX,
+            lookahead: VecDeque::new(),
+            lossless: false,
             reader: reader.peekable(),
             position: Position::default(),
-            source: Rc::new(source.as_ref().into()),
+            source,
+            start: Position::default(),
+            trace: Vec::new(),
+        }
+    }
+
+    /// Stops the tokens with a critical diagnostic at the first one longer
+    /// than `limit` bytes.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Creates a tokenizer that also yields whitespace and newlines as
+    /// [`Trivia`], so the tokens cover the whole source.
+    pub fn lossless(source: Rc<Source>, reader: T) -> Self {
+        Self {
+            lossless: true,
+            ..Self::new(source, reader)
         }
     }
 }
 
-pub struct Configurator<'a>(pub &'a str);
+pub struct Configurator(pub Rc<Source>);
 
-impl<'a, I> ConfigurableProcessor<I> for Configurator<'a>
+impl<I> ConfigurableProcessor<I> for Configurator
 where
     I: Iterator<Item = char>,
 {
@@ -343,3 +875,152 @@ where
         Self::Iterator::new(self.0, upstream)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::analyzer::{
+        lexical::{Kind, Lexeme, Token},
+        source::Source,
+    };
+
+    use super::Analyzer;
+
+    #[test]
+    fn test() {
+        let source = Source::string(
+            "test.mt",
+            r#"p "a\n\t\\\"\'\u{1F600}" 'b\q' "\u{D800}\u{}\u12" 'open"#,
+        );
+
+        let tokens: Vec<Token> = Analyzer::new(source.clone(), source.chars()).collect();
+
+        #[rustfmt::skip]
+        let literals: Vec<&str> = tokens.iter().filter_map(|token| match &token.kind {
+            Kind::Lexeme(Lexeme::Literal(value)) => Some(value.as_str()),
+            _ => None,
+        }).collect();
+
+        assert_eq!(literals, ["a\n\t\\\"'\u{1F600}", "b\\q", "12", "open"]);
+
+        #[rustfmt::skip]
+        let messages: Vec<(&str, &str)> = tokens.iter().flat_map(|token| &token.trace).map(|diagnostic| {
+            (diagnostic.message.as_str(), diagnostic.span.text())
+        }).collect();
+
+        assert_eq!(
+            messages,
+            [
+                ("unknown escape sequence `\\q`", "\\q"),
+                ("'D800' is not a unicode character", "\\u{D800}"),
+                ("a unicode escape has from 1 to 6 hex digits", "\\u{}"),
+                ("a unicode escape is written as `\\u{...}`", "\\u"),
+                ("this literal is never closed", "'"),
+            ]
+        );
+
+        let source = Source::string(
+            "test.mt",
+            "pre { r#\"a \"quoted\" \\n\"# r'b' \"\" \"\"\"\n      fn main() {\n\n          \"\"\\t\n      }\n    \"\"\" }",
+        );
+
+        let tokens: Vec<Token> = Analyzer::new(source.clone(), source.chars()).collect();
+
+        #[rustfmt::skip]
+        let literals: Vec<&str> = tokens.iter().filter_map(|token| match &token.kind {
+            Kind::Lexeme(Lexeme::Literal(value)) => Some(value.as_str()),
+            _ => None,
+        }).collect();
+
+        assert_eq!(
+            literals,
+            ["a \"quoted\" \\n", "b", "", "fn main() {\n\n    \"\"\t\n}"]
+        );
+        assert!(tokens.iter().all(|token| token.trace.is_empty()));
+
+        let source = Source::string(
+            "test.mt",
+            "12 -3 1.5 2e3 true null 9223372036854775808 100px -1e999",
+        );
+
+        let tokens: Vec<Token> = Analyzer::new(source.clone(), source.chars()).collect();
+        let kinds: Vec<&Kind> = tokens.iter().map(|token| &token.kind).collect();
+
+        assert_eq!(
+            kinds[..6],
+            [
+                &Kind::Lexeme(Lexeme::Integer(12)),
+                &Kind::Lexeme(Lexeme::Integer(-3)),
+                &Kind::Lexeme(Lexeme::Float(1.5)),
+                &Kind::Lexeme(Lexeme::Float(2000.0)),
+                &Kind::Lexeme(Lexeme::Boolean(true)),
+                &Kind::Lexeme(Lexeme::Null),
+            ]
+        );
+
+        assert_eq!(
+            tokens[6].trace[0].message,
+            "the integer 9223372036854775808 does not fit in 64 bits"
+        );
+        assert_eq!(
+            tokens[7].kind,
+            Kind::Lexeme(Lexeme::Literal("100px".into()))
+        );
+        assert_eq!(
+            tokens[8].trace[0].message,
+            "the number -1e999 does not fit in a 64-bit float"
+        );
+
+        let source = Source::string("test.mt", "#* a #* b *# c\n*# p ## doc\n#* open");
+
+        let tokens: Vec<Token> = Analyzer::new(source.clone(), source.chars()).collect();
+        let kinds: Vec<&Kind> = tokens.iter().map(|token| &token.kind).collect();
+
+        assert_eq!(
+            kinds,
+            [
+                &Kind::Lexeme(Lexeme::Comment("#* a #* b *# c\n*#".into())),
+                &Kind::Lexeme(Lexeme::Identifier("p".into())),
+                &Kind::Lexeme(Lexeme::Comment("## doc".into())),
+                &Kind::Lexeme(Lexeme::Comment("#* open".into())),
+            ]
+        );
+        assert_eq!(tokens[3].trace[0].message, "this comment is never closed");
+        assert_eq!(tokens[3].trace[0].span.text(), "#*");
+
+        let source = Source::string(
+            "test.mt",
+            "#*****
+#* a #*b *#
+p",
+        );
+
+        let tokens: Vec<Token> = Analyzer::new(source.clone(), source.chars()).collect();
+        let kinds: Vec<&Kind> = tokens.iter().map(|token| &token.kind).collect();
+
+        assert_eq!(
+            kinds,
+            [
+                &Kind::Lexeme(Lexeme::Comment("#*****".into())),
+                &Kind::Lexeme(Lexeme::Comment("#* a #*b *#".into())),
+                &Kind::Lexeme(Lexeme::Identifier("p".into())),
+            ]
+        );
+
+        let source = Source::string("test.mt", "r# note\nr##'x'## r.card div# x");
+
+        let tokens: Vec<Token> = Analyzer::new(source.clone(), source.chars()).collect();
+        let kinds: Vec<&Kind> = tokens.iter().map(|token| &token.kind).collect();
+
+        assert_eq!(
+            kinds,
+            [
+                &Kind::Lexeme(Lexeme::Identifier("r".into())),
+                &Kind::Lexeme(Lexeme::Comment("# note".into())),
+                &Kind::Lexeme(Lexeme::Literal("x".into())),
+                &Kind::Lexeme(Lexeme::Identifier("r.card".into())),
+                &Kind::Lexeme(Lexeme::Identifier("div".into())),
+                &Kind::Lexeme(Lexeme::Comment("# x".into())),
+            ]
+        );
+    }
+}
@@ -2,4 +2,93 @@ pub mod diagnostic;
 pub mod filesystem;
 pub mod lexical;
 pub mod semantic;
+pub mod source;
 pub mod syntactic;
+
+use std::rc::Rc;
+
+use iterator_stage::Stage;
+
+use self::{
+    lexical::tokenizer,
+    semantic::{attribute, element, ownership, schema::Schema},
+    source::{Chars, Source},
+    syntactic::{
+        converter, desugaring, expansion, inclusion,
+        limits::{self, Limits},
+    },
+};
+
+/// The configuration shared by every analysis stage.
+#[derive(Clone, Debug)]
+pub struct Pipeline {
+    /// Whether includes read the files they name; input that is not trusted to
+    /// read files has every include reported instead.
+    pub includes: bool,
+    pub limits: Limits,
+    pub schema: Option<Rc<Schema>>,
+}
+
+impl Pipeline {
+    /// Runs every analysis stage over `source`, yielding the checked node stream.
+    pub fn analyze(&self, source: &Rc<Source>) -> impl Iterator<Item = syntactic::Result> {
+        let upstream = tokenizer::Analyzer::<Chars>::new(source.clone(), source.chars())
+            .with_limit(self.limits.length);
+
+        let upstream = converter::Analyzer::new(upstream).with_limit(self.limits.depth);
+
+        #[rustfmt::skip]
+        let upstream = match self.includes {
+            true => inclusion::Analyzer::new(upstream, source, self.limits),
+            false => inclusion::Analyzer::new(upstream, source, self.limits).without_includes(),
+        };
+
+        let upstream = upstream.chain_infer::<desugaring::Analyzer<_>>();
+
+        let upstream = expansion::Analyzer::with_limits(upstream, self.limits);
+
+        let upstream = limits::Analyzer::new(upstream, source.clone(), self.limits);
+
+        #[rustfmt::skip]
+        let upstream = match &self.schema {
+            Some(schema) => attribute::Analyzer::with_schema(upstream, schema.clone()),
+            None => attribute::Analyzer::new(upstream),
+        };
+
+        #[rustfmt::skip]
+        let upstream = match &self.schema {
+            Some(schema) => element::Analyzer::with_schema(upstream, schema.clone()),
+            None => element::Analyzer::new(upstream),
+        };
+
+        upstream.chain_infer::<ownership::Analyzer<_>>()
+    }
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self {
+            includes: true,
+            limits: Limits::default(),
+            schema: None,
+        }
+    }
+
+    pub fn with_schema(schema: Rc<Schema>) -> Self {
+        Self {
+            schema: Some(schema),
+            ..Self::new()
+        }
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs every analysis stage over `source` with the default [`Pipeline`].
+pub fn analyze(source: &Rc<Source>) -> impl Iterator<Item = syntactic::Result> {
+    Pipeline::new().analyze(source)
+}
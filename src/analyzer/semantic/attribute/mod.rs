@@ -1,18 +1,40 @@
-use std::{collections::VecDeque, iter::Peekable, rc::Rc};
+use std::{
+    collections::{HashSet, VecDeque},
+    iter::Peekable,
+    rc::Rc,
+};
 
-use iterator_stage::Processor;
+use iterator_stage::{ConfigurableProcessor, Processor};
 
 use crate::analyzer::{
     diagnostic::{self, Diagnostic},
-    syntactic::{Element, Kind, Node, ProcessingInstruction, Result},
+    semantic::schema::{Merge, Schema},
+    syntactic::{Attribute, Element, Kind, Node, ProcessingInstruction, Result, Value},
 };
 
+struct Frame {
+    attributes: Vec<Rc<Node>>,
+    depth: u16,
+    element: Option<Rc<Node>>,
+    merged: Vec<Rc<Node>>,
+}
+
+fn attribute_name(node: &Node) -> Option<&str> {
+    match &node.kind {
+        Kind::Attribute(attribute) => Some(&attribute.name),
+        _ => None,
+    }
+}
+
 pub struct Analyzer<T>
 where
     T: Iterator<Item = Result>,
 {
+    pending: VecDeque<Result>,
+    previous: Option<Rc<Node>>,
     reader: Peekable<T>,
-    stack: Vec<Rc<Node>>,
+    schema: Option<Rc<Schema>>,
+    stack: Vec<Frame>,
     trace: VecDeque<Diagnostic>,
 }
 
@@ -20,11 +42,13 @@ impl<T> Analyzer<T>
 where
     T: Iterator<Item = Result>,
 {
-    fn validate_attribute_context(&mut self, _: &Node) {
-        while let Some(node) = self.stack.pop() {
-            if let Kind::ProcessingInstruction(ProcessingInstruction::Borrow(_)) | Kind::Text(_) =
-                &node.kind
-            {
+    fn validate_attribute_context(&mut self, node: &Node) {
+        let Some(previous) = &self.previous else {
+            return;
+        };
+
+        if let Kind::Attribute(_) = previous.kind {
+            if previous.depth == node.depth {
                 let message = format!("attribute cannot be followed by a {}", node.kind);
                 let diagnostic =
                     Diagnostic::new(diagnostic::Kind::Error, &message, node.span.clone());
@@ -34,8 +58,259 @@ where
         }
     }
 
-    fn validate_attribute_name_and_value(&mut self, _element: &Element) {
-        while let Some(_node) = self.stack.pop() {}
+    fn validate_attribute_name_and_value(
+        &mut self,
+        node: &Node,
+        element: &Element,
+        attributes: &[Rc<Node>],
+    ) {
+        let Some(schema) = self.schema.clone() else {
+            return;
+        };
+
+        let Some(declaration) = schema.element(&element.name) else {
+            return;
+        };
+
+        for attribute in attributes {
+            let Kind::Attribute(data) = &attribute.kind else {
+                continue;
+            };
+
+            let Some(expected) = schema
+                .attributes(declaration)
+                .find(|expected| expected.name == data.name)
+            else {
+                let message = format!(
+                    "the attribute '{}' is not declared for the element '{}'",
+                    data.name, element.name
+                );
+
+                self.report(&message, attribute);
+
+                continue;
+            };
+
+            if !expected.value.accepts(data.value.as_ref()) {
+                #[rustfmt::skip]
+                let message = match &data.value {
+                    Some(value) => format!("the attribute '{}' expects {}, found '{}'", data.name, expected.value, value),
+                    None => format!("the attribute '{}' expects {}, but has no value", data.name, expected.value),
+                };
+
+                self.report(&message, attribute);
+            }
+        }
+
+        for expected in schema.attributes(declaration) {
+            let present = attributes.iter().any(|attribute| {
+                matches!(&attribute.kind, Kind::Attribute(data) if data.name == expected.name)
+            });
+
+            if present {
+                continue;
+            }
+
+            if let Some(default) = &expected.default {
+                let data = Attribute::new(expected.name.clone(), Some(default.clone()));
+                let attribute = Node::new(node.depth, data, node.span.clone());
+
+                self.pending.push_back(Result::from(attribute));
+            } else if expected.required {
+                let message = format!(
+                    "the element '{}' requires the attribute '{}'",
+                    element.name, expected.name
+                );
+
+                self.report(&message, node);
+            }
+        }
+    }
+
+    fn merge(&mut self, element: &Node, merged: &[Rc<Node>]) {
+        let mut seen = HashSet::new();
+
+        let names: Vec<&str> = merged
+            .iter()
+            .filter_map(|node| attribute_name(node))
+            .filter(|name| seen.insert(*name))
+            .collect();
+
+        for name in names {
+            let Some(first) = merged
+                .iter()
+                .find(|node| attribute_name(node) == Some(name))
+            else {
+                continue;
+            };
+
+            let separator = match self.policy(&element.kind, name) {
+                Merge::Join(separator) => separator,
+                Merge::Reject => continue,
+            };
+
+            #[rustfmt::skip]
+            let values: Vec<String> = merged.iter().filter_map(|node| match &node.kind {
+                Kind::Attribute(attribute) if attribute.name == name => attribute.value.as_ref().map(Value::to_string),
+                _ => None,
+            }).collect();
+
+            let value = (!values.is_empty()).then(|| Value::String(values.join(&separator)));
+
+            let data = Attribute::new(name.to_string(), value);
+            let attribute = Node::new(first.depth, data, first.span.clone());
+
+            self.pending.push_back(Result::from(attribute));
+        }
+    }
+
+    /// How the attribute `name` of `element` merges; without a schema the
+    /// classes of the selector shorthand join those written out.
+    fn policy(&self, element: &Kind, name: &str) -> Merge {
+        let Kind::Element(element) = element else {
+            return Merge::Reject;
+        };
+
+        let Some(schema) = &self.schema else {
+            return match name {
+                "class" => Merge::Join(" ".into()),
+                _ => Merge::Reject,
+            };
+        };
+
+        schema
+            .attribute(&element.name, name)
+            .map(|declaration| declaration.merge.clone())
+            .unwrap_or_default()
+    }
+
+    fn report(&mut self, message: &str, node: &Node) {
+        let diagnostic = Diagnostic::new(diagnostic::Kind::Error, message, node.span.clone());
+
+        self.trace.push_back(diagnostic);
+    }
+}
+
+impl<T> Analyzer<T>
+where
+    T: Iterator<Item = Result>,
+{
+    fn close(&mut self) {
+        let Some(frame) = self.stack.pop() else {
+            return;
+        };
+
+        if let Some(node) = &frame.element {
+            if let Kind::Element(element) = &node.kind {
+                self.validate_attribute_name_and_value(node, element, &frame.attributes);
+            }
+
+            self.merge(node, &frame.merged);
+        }
+    }
+
+    fn close_until(&mut self, depth: u16) {
+        while self.stack.last().is_some_and(|frame| frame.depth > depth) {
+            self.close();
+        }
+    }
+
+    fn close_siblings(&mut self, depth: u16) {
+        while self.stack.last().is_some_and(|frame| frame.depth >= depth) {
+            self.close();
+        }
+    }
+
+    fn open(&mut self, node: &Rc<Node>, element: Option<Rc<Node>>) {
+        self.close_siblings(node.depth);
+
+        self.stack.push(Frame {
+            attributes: Vec::new(),
+            depth: node.depth,
+            element,
+            merged: Vec::new(),
+        });
+    }
+
+    /// Records an attribute on the element it belongs to, returning whether it
+    /// is forwarded now or held back to be merged once the element closes.
+    fn collect(&mut self, node: &Rc<Node>) -> bool {
+        self.close_until(node.depth);
+
+        let Some(name) = attribute_name(node) else {
+            return true;
+        };
+
+        let Some(element) = self.stack.last().and_then(|frame| frame.element.clone()) else {
+            return true;
+        };
+
+        let policy = self.policy(&element.kind, name);
+
+        let Some(frame) = self.stack.last_mut() else {
+            return true;
+        };
+
+        let first = frame
+            .attributes
+            .iter()
+            .find(|attribute| attribute_name(attribute) == Some(name))
+            .cloned();
+
+        frame.attributes.push(Rc::clone(node));
+
+        if let Merge::Join(_) = policy {
+            frame.merged.push(Rc::clone(node));
+
+            return false;
+        }
+
+        if let Some(first) = first {
+            let message = format!("the attribute '{}' is declared more than once", name);
+            let diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, node.span.clone())
+                .with_label(first.span.clone(), "first declared here");
+
+            self.trace.push_back(diagnostic);
+        }
+
+        true
+    }
+
+    /// Tracks `node`, returning whether it is forwarded downstream.
+    fn inspect(&mut self, node: &Rc<Node>) -> bool {
+        let forward = match &node.kind {
+            Kind::Attribute(_) => self.collect(node),
+            Kind::Comment(_) => {
+                self.close_siblings(node.depth);
+
+                true
+            }
+            Kind::Element(_) => {
+                self.open(node, Some(Rc::clone(node)));
+
+                true
+            }
+            Kind::ProcessingInstruction(ProcessingInstruction::Borrowable(_)) => {
+                self.open(node, None);
+
+                true
+            }
+            Kind::ProcessingInstruction(
+                ProcessingInstruction::Borrow(_) | ProcessingInstruction::Include(_),
+            )
+            | Kind::Text(_) => {
+                self.validate_attribute_context(node);
+                self.close_siblings(node.depth);
+
+                true
+            }
+        };
+
+        if !matches!(node.kind, Kind::Comment(_)) {
+            self.previous = Some(Rc::clone(node));
+        }
+
+        forward
     }
 }
 
@@ -46,28 +321,34 @@ where
     type Item = Result;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(diagnostic) = self.trace.pop_front() {
-            return Some(Result::from(diagnostic));
-        }
+        loop {
+            if let Some(diagnostic) = self.trace.pop_front() {
+                return Some(Result::from(diagnostic));
+            }
 
-        let result = self.reader.next();
+            if let Some(result) = self.pending.pop_front() {
+                return Some(result);
+            }
 
-        if let Some(Result::Value(node)) = &result {
-            match &node.kind {
-                Kind::Attribute(_) => {
-                    self.stack.push(Rc::clone(node));
+            match self.reader.next() {
+                Some(Result::Value(node)) => {
+                    if self.inspect(&node) {
+                        self.pending.push_back(Result::Value(node));
+                    }
+                }
+                Some(result) => {
+                    self.pending.push_back(result);
                 }
-                Kind::ProcessingInstruction(ProcessingInstruction::Borrow(_)) | Kind::Text(_) => {
-                    self.validate_attribute_context(&node);
+                None if self.stack.is_empty() => {
+                    return None;
                 }
-                Kind::Element(element) => {
-                    self.validate_attribute_name_and_value(&element);
+                None => {
+                    while !self.stack.is_empty() {
+                        self.close();
+                    }
                 }
-                _ => {}
             }
         }
-
-        result
     }
 }
 
@@ -77,11 +358,21 @@ where
 {
     pub fn new(reader: T) -> Self {
         Self {
+            pending: VecDeque::new(),
+            previous: None,
             reader: reader.peekable(),
+            schema: None,
             stack: Vec::new(),
             trace: VecDeque::new(),
         }
     }
+
+    pub fn with_schema(reader: T, schema: Rc<Schema>) -> Self {
+        Self {
+            schema: Some(schema),
+            ..Self::new(reader)
+        }
+    }
 }
 
 impl<T> Processor<T> for Analyzer<T>
@@ -92,3 +383,113 @@ where
         Analyzer::new(upstream)
     }
 }
+
+pub struct Configurator(pub Rc<Schema>);
+
+impl<T> ConfigurableProcessor<T> for Configurator
+where
+    T: Iterator<Item = Result>,
+{
+    type Iterator = Analyzer<T>;
+
+    fn build(self, upstream: T) -> Self::Iterator {
+        Self::Iterator::with_schema(upstream, self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::analyzer::{
+        lexical::tokenizer,
+        semantic::schema::Schema,
+        source::Source,
+        syntactic::{converter, desugaring, Kind, Result, Value},
+    };
+
+    use super::Analyzer;
+
+    #[test]
+    fn test() {
+        let schema = Source::string(
+            "schema.mt",
+            "attribute | name \"class\" | merge \"join\" attribute | name \"rel\" | merge \"join\" element | name \"img\" { attribute | name \"src\" | type \"url\" | required attribute | name \"width\" | type \"integer\" | default 100 }",
+        );
+
+        let (schema, diagnostics) = Schema::parse(&schema);
+
+        assert!(diagnostics.is_empty());
+
+        let source = Source::string(
+            "test.mt",
+            "img | width \"wide\" img | src \"a.png\" | class \"a\" | src \"b.png\" | class \"b\" a | class \"a\" | rel \"x\" | class \"b\" | rel \"y\"",
+        );
+
+        let tokens = tokenizer::Analyzer::new(source.clone(), source.chars());
+        let analyzer = Analyzer::with_schema(converter::Analyzer::new(tokens), Rc::new(schema));
+
+        let mut messages = Vec::new();
+        let mut attributes = Vec::new();
+
+        for result in analyzer {
+            match result {
+                Result::Diagnostic(diagnostic) => messages.push(diagnostic.message),
+                Result::Value(node) => {
+                    if let Kind::Attribute(attribute) = &node.kind {
+                        attributes.push((attribute.name.clone(), attribute.value.clone()));
+                    }
+                }
+            }
+        }
+
+        assert_eq!(
+            messages,
+            [
+                "the attribute 'width' expects an integer, found 'wide'",
+                "the element 'img' requires the attribute 'src'",
+                "the attribute 'src' is declared more than once",
+            ]
+        );
+
+        assert_eq!(
+            attributes[attributes.len() - 4..],
+            [
+                ("width".to_string(), Some(Value::Integer(100))),
+                ("class".to_string(), Some(Value::from("a b"))),
+                ("class".to_string(), Some(Value::from("a b"))),
+                ("rel".to_string(), Some(Value::from("x y"))),
+            ]
+        );
+
+        let source = Source::string("test.mt", "div.card | class \"x\" | id \"a\" | id \"b\"");
+
+        let tokens = tokenizer::Analyzer::new(source.clone(), source.chars());
+        let analyzer = Analyzer::new(desugaring::Analyzer::new(converter::Analyzer::new(tokens)));
+
+        let mut messages = Vec::new();
+        let mut attributes = Vec::new();
+
+        for result in analyzer {
+            match result {
+                Result::Diagnostic(diagnostic) => messages.push(diagnostic.message),
+                Result::Value(node) => {
+                    if let Kind::Attribute(attribute) = &node.kind {
+                        attributes.push((attribute.name.clone(), attribute.value.clone()));
+                    }
+                }
+            }
+        }
+
+        assert_eq!(messages, ["the attribute 'id' is declared more than once"]);
+
+        assert_eq!(
+            attributes,
+            [
+                ("id".to_string(), Some(Value::from("a"))),
+                ("id".to_string(), Some(Value::from("b"))),
+                ("class".to_string(), Some(Value::from("card x"))),
+            ]
+        );
+    }
+}
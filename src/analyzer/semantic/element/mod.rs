@@ -1,17 +1,187 @@
-use std::{collections::VecDeque, iter::Peekable};
+use std::{collections::VecDeque, iter::Peekable, rc::Rc};
 
-use iterator_stage::Processor;
+use iterator_stage::{ConfigurableProcessor, Processor};
 
-use crate::analyzer::{diagnostic::Diagnostic, syntactic::Result};
+use crate::analyzer::{
+    diagnostic::{self, Diagnostic, Span},
+    semantic::schema::Schema,
+    syntactic::{Kind, Node, ProcessingInstruction, Result},
+};
+
+struct Frame {
+    /// The elements declared by the borrowable blocks written directly inside
+    /// this element.
+    borrowables: Vec<String>,
+    children: Vec<String>,
+    depth: u16,
+    name: Option<String>,
+    span: Span,
+}
 
 pub struct Analyzer<T>
 where
     T: Iterator<Item = Result>,
 {
+    /// The elements declared by the borrowable blocks at the top level.
+    borrowables: Vec<String>,
     reader: Peekable<T>,
+    schema: Option<Rc<Schema>>,
+    stack: Vec<Frame>,
     trace: VecDeque<Diagnostic>,
 }
 
+impl<T> Analyzer<T>
+where
+    T: Iterator<Item = Result>,
+{
+    fn report(&mut self, message: &str, span: &Span) {
+        let diagnostic = Diagnostic::new(diagnostic::Kind::Error, message, span.clone());
+
+        self.trace.push_back(diagnostic);
+    }
+
+    fn close(&mut self, schema: &Schema) {
+        let Some(frame) = self.stack.pop() else {
+            return;
+        };
+
+        let Some(element) = frame.name.as_deref().and_then(|name| schema.element(name)) else {
+            return;
+        };
+
+        for required in &element.required {
+            if !frame.children.contains(required) {
+                let message = format!(
+                    "the element '{}' requires a '{}' child",
+                    element.name, required
+                );
+
+                self.report(&message, &frame.span);
+            }
+        }
+    }
+
+    fn close_until(&mut self, schema: &Schema, depth: u16) {
+        while self.stack.last().is_some_and(|frame| frame.depth >= depth) {
+            self.close(schema);
+        }
+    }
+
+    /// Checks that `name` may appear inside the innermost open element. Elements
+    /// declared in a borrowable block are only checked where they are borrowed.
+    fn validate_placement(&mut self, schema: &Schema, name: &str, span: &Span) {
+        let Some(frame) = self.stack.last_mut() else {
+            if !schema.allows_root(name) {
+                let message = format!("the element '{}' is not allowed at the top level", name);
+
+                self.report(&message, span);
+            }
+
+            return;
+        };
+
+        frame.children.push(name.to_string());
+
+        let Some(parent) = frame.name.as_deref().and_then(|name| schema.element(name)) else {
+            return;
+        };
+
+        if !parent.allows(name) {
+            let message = format!(
+                "the element '{}' is not allowed inside '{}'",
+                name, parent.name
+            );
+
+            self.report(&message, span);
+        }
+    }
+
+    /// Finds the element a borrow refers to among the borrowables declared so
+    /// far around it.
+    fn resolve(&self, name: &str) -> Option<String> {
+        #[rustfmt::skip]
+        let mut borrowables = self.stack.iter().rev().map(|frame| &frame.borrowables).chain([&self.borrowables]);
+
+        borrowables.find_map(|names| names.iter().find(|borrowable| *borrowable == name).cloned())
+    }
+
+    fn validate_element(&mut self, schema: &Schema, name: &str, node: &Node) {
+        self.close_until(schema, node.depth);
+
+        if let [.., owner, Frame { name: None, .. }] = self.stack.as_mut_slice() {
+            owner.borrowables.push(name.to_string());
+        } else if let [Frame { name: None, .. }] = self.stack.as_slice() {
+            self.borrowables.push(name.to_string());
+        }
+
+        if schema.element(name).is_none() {
+            let message = format!("the element '{}' is not declared in the schema", name);
+
+            self.report(&message, &node.span);
+        } else {
+            self.validate_placement(schema, name, &node.span);
+        }
+
+        self.stack.push(Frame {
+            borrowables: Vec::new(),
+            children: Vec::new(),
+            depth: node.depth,
+            name: Some(name.to_string()),
+            span: node.span.clone(),
+        });
+    }
+
+    fn validate_text(&mut self, schema: &Schema, node: &Node) {
+        self.close_until(schema, node.depth);
+
+        let Some(frame) = self.stack.last() else {
+            return;
+        };
+
+        if let Some(element) = frame.name.as_deref().and_then(|name| schema.element(name)) {
+            if !element.text {
+                let message = format!("the element '{}' cannot hold text", element.name);
+
+                self.report(&message, &node.span);
+            }
+        }
+    }
+
+    fn validate(&mut self, schema: &Schema, node: &Node) {
+        match &node.kind {
+            Kind::Element(element) => {
+                self.validate_element(schema, &element.name, node);
+            }
+            Kind::ProcessingInstruction(ProcessingInstruction::Borrow(borrow)) => {
+                self.close_until(schema, node.depth);
+
+                // A borrow that names no borrowable is reported by the
+                // ownership analyzer, and says nothing about what is placed.
+                if let Some(name) = self.resolve(&borrow.name) {
+                    self.validate_placement(schema, &name, &node.span);
+                }
+            }
+            Kind::ProcessingInstruction(ProcessingInstruction::Borrowable(_)) => {
+                self.close_until(schema, node.depth);
+
+                self.stack.push(Frame {
+                    borrowables: Vec::new(),
+                    children: Vec::new(),
+                    depth: node.depth,
+                    name: None,
+                    span: node.span.clone(),
+                });
+            }
+            Kind::Text(_) => {
+                self.validate_text(schema, node);
+            }
+            Kind::Attribute(_)
+            | Kind::Comment(_)
+            | Kind::ProcessingInstruction(ProcessingInstruction::Include(_)) => {}
+        }
+    }
+}
+
 impl<T> Iterator for Analyzer<T>
 where
     T: Iterator<Item = Result>,
@@ -25,9 +195,19 @@ where
 
         let result = self.reader.next();
 
-        if let Some(Result::Value(node)) = &result {
-            match &node.kind {
-                _ => {}
+        if let Some(schema) = self.schema.clone() {
+            match &result {
+                Some(Result::Value(node)) => {
+                    self.validate(&schema, node);
+                }
+                Some(Result::Diagnostic(_)) => {}
+                None => {
+                    while !self.stack.is_empty() {
+                        self.close(&schema);
+                    }
+
+                    return self.trace.pop_front().map(Result::from);
+                }
             }
         }
 
@@ -41,10 +221,20 @@ where
 {
     pub fn new(reader: T) -> Self {
         Self {
+            borrowables: Vec::new(),
             reader: reader.peekable(),
+            schema: None,
+            stack: Vec::new(),
             trace: VecDeque::new(),
         }
     }
+
+    pub fn with_schema(reader: T, schema: Rc<Schema>) -> Self {
+        Self {
+            schema: Some(schema),
+            ..Self::new(reader)
+        }
+    }
 }
 
 impl<T> Processor<T> for Analyzer<T>
@@ -55,3 +245,66 @@ where
         Analyzer::new(upstream)
     }
 }
+
+pub struct Configurator(pub Rc<Schema>);
+
+impl<T> ConfigurableProcessor<T> for Configurator
+where
+    T: Iterator<Item = Result>,
+{
+    type Iterator = Analyzer<T>;
+
+    fn build(self, upstream: T) -> Self::Iterator {
+        Self::Iterator::with_schema(upstream, self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::analyzer::{
+        lexical::tokenizer,
+        semantic::schema::Schema,
+        source::Source,
+        syntactic::{converter, Result},
+    };
+
+    use super::Analyzer;
+
+    #[test]
+    fn test() {
+        let schema = Source::string(
+            "schema.mt",
+            "root { ul } element | name \"ul\" { children { li } required { li } } element | name \"li\" | text",
+        );
+
+        let (schema, diagnostics) = Schema::parse(&schema);
+
+        assert!(diagnostics.is_empty());
+
+        let source = Source::string(
+            "test.mt",
+            "ul { li { \"a\" } p } ul li { \"b\" } items -> { li { \"c\" } } ul { li <- } ul { nav <- }",
+        );
+
+        let tokens = tokenizer::Analyzer::new(source.clone(), source.chars());
+        let analyzer = Analyzer::with_schema(converter::Analyzer::new(tokens), Rc::new(schema));
+
+        #[rustfmt::skip]
+        let messages: Vec<String> = analyzer.filter_map(|result| match result {
+            Result::Diagnostic(diagnostic) => Some(diagnostic.message),
+            Result::Value(_) => None,
+        }).collect();
+
+        assert_eq!(
+            messages,
+            [
+                "the element 'p' is not declared in the schema",
+                "the element 'ul' requires a 'li' child",
+                "the element 'li' is not allowed at the top level",
+                "the element 'ul' requires a 'li' child",
+            ]
+        );
+    }
+}
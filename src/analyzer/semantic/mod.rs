@@ -1,3 +1,4 @@
 pub mod attribute;
 pub mod element;
 pub mod ownership;
+pub mod schema;
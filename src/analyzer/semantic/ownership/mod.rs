@@ -11,6 +11,7 @@ use crate::analyzer::{
 };
 
 struct BorrowChecker {
+    origins: Vec<Node>,
     scope: Vec<VecDeque<Node>>,
     trace: Vec<HashMap<String, Node>>,
     transferring: u16,
@@ -43,11 +44,13 @@ impl BorrowChecker {
 
         while let Some(deque) = scope.last_mut() {
             while let Some(borrowable) = deque.pop_front() {
-                if borrowable.depth - 1 > node.depth {
+                let depth = borrowable.depth.checked_sub(1);
+
+                if depth.is_some_and(|depth| depth > node.depth) {
                     break;
                 }
 
-                if borrowable.depth - 1 == node.depth {
+                if depth == Some(node.depth) {
                     if let Kind::Element(element) = &borrowable.kind {
                         if element.name == borrow.name {
                             return true;
@@ -66,10 +69,11 @@ impl BorrowChecker {
 }
 
 impl BorrowChecker {
-    pub fn borrowable(&mut self) {
-        self.transferring += 1;
+    pub fn borrowable(&mut self, node: &Node) {
+        self.transferring = self.transferring.saturating_add(1);
 
         if self.transferring == 1 {
+            self.origins.push(node.clone());
             self.scope.push(VecDeque::new());
             self.trace.push(HashMap::new());
         }
@@ -78,11 +82,13 @@ impl BorrowChecker {
     fn borrowing(&mut self, borrow: &Borrow, node: &Node) {
         while let Some(deque) = self.scope.last_mut() {
             while let Some(borrowable) = deque.pop_front() {
-                if borrowable.depth > node.depth + 1 {
+                let depth = u32::from(node.depth) + 1;
+
+                if u32::from(borrowable.depth) > depth {
                     break;
                 }
 
-                if borrowable.depth == node.depth + 1 {
+                if u32::from(borrowable.depth) == depth {
                     if let Kind::Element(element) = &borrowable.kind {
                         if let Some(trace) = self.trace.last_mut() {
                             trace.insert(element.name.clone(), node.clone());
@@ -95,6 +101,7 @@ impl BorrowChecker {
                 }
             }
 
+            self.origins.pop();
             self.scope.pop();
             self.trace.pop();
         }
@@ -107,12 +114,33 @@ impl BorrowChecker {
             return None;
         }
 
+        let origin = self.origins.get(node.depth as usize);
+
         if let Some(trace) = self.trace.get(node.depth as usize) {
-            if let Some(_) = trace.get(&borrow.name) {
+            if let Some(previous) = trace.get(&borrow.name) {
                 let message = format!("the borrowable '{}' has been already borrowed", borrow.name);
 
+                #[rustfmt::skip]
+                let label = match &previous.kind {
+                    Kind::ProcessingInstruction(ProcessingInstruction::Borrow(other)) if other.name != borrow.name => {
+                        format!("released when '{}' was borrowed here", other.name)
+                    }
+                    _ => "first borrowed here".to_string(),
+                };
+
                 let kind = diagnostic::Kind::Error;
-                let diagnostic = Diagnostic::new(kind, &message, node.span.clone());
+                let mut diagnostic = Diagnostic::new(kind, &message, node.span.clone())
+                    .with_primary_label("borrowed again here")
+                    .with_label(previous.span.clone(), &label);
+
+                if let Some(origin) = origin {
+                    diagnostic =
+                        diagnostic.with_label(origin.span.clone(), "declared in this borrowable");
+                }
+
+                let diagnostic = diagnostic.with_note(
+                    "borrowables are taken in the order they are declared, releasing the ones before them",
+                );
 
                 return Some(diagnostic);
             }
@@ -126,13 +154,20 @@ impl BorrowChecker {
         let kind = diagnostic::Kind::Error;
         let diagnostic = Diagnostic::new(kind, &message, node.span.clone());
 
-        return Some(diagnostic);
+        #[rustfmt::skip]
+        let diagnostic = match origin {
+            Some(origin) => diagnostic.with_label(origin.span.clone(), &format!("no element '{}' is left in this borrowable", borrow.name)),
+            None => diagnostic.with_help(&format!("declare '{}' inside a `->` block before borrowing it", borrow.name)),
+        };
+
+        Some(diagnostic)
     }
 }
 
 impl BorrowChecker {
     pub fn new() -> Self {
         Self {
+            origins: Vec::new(),
             scope: Vec::new(),
             trace: Vec::new(),
             transferring: 0,
@@ -174,8 +209,9 @@ where
                         }
                     }
                     ProcessingInstruction::Borrowable(_) => {
-                        self.checker.borrowable();
+                        self.checker.borrowable(node);
                     }
+                    ProcessingInstruction::Include(_) => {}
                 },
                 _ => {}
             }
@@ -216,9 +252,9 @@ mod test {
     use iterator_stage::Stage;
 
     use crate::analyzer::{
-        filesystem::FileReader,
         lexical::tokenizer,
         semantic::{attribute, element, ownership},
+        source::Source,
         syntactic::{converter, desugaring},
     };
 
@@ -226,9 +262,9 @@ mod test {
     fn test() -> std::io::Result<()> {
         let path = "/home/flames/Github/FlamesX-128/markup-type/examples/input.mt";
 
-        let reader = FileReader::new(&path)?;
+        let (source, _) = Source::file(&path, None)?;
 
-        let mut analyzer = tokenizer::Analyzer::new(&path, reader)
+        let mut analyzer = tokenizer::Analyzer::new(source.clone(), source.chars())
             .chain_infer::<converter::Analyzer<_>>()
             .chain_infer::<desugaring::Analyzer<_>>()
             .chain_infer::<attribute::Analyzer<_>>()
@@ -0,0 +1,477 @@
+use std::{collections::HashMap, rc::Rc};
+
+use regex::Regex;
+
+use crate::analyzer::{
+    self,
+    diagnostic::{self, Diagnostic, Span},
+    source::Source,
+    syntactic::{
+        tree::{self, Builder, Document, Node},
+        Value,
+    },
+};
+
+/// The type of an attribute value; a `Pattern` is anchored to the whole value.
+#[derive(Clone, Debug, Default)]
+pub enum Type {
+    Boolean,
+    Enum(Vec<String>),
+    Float,
+    Integer,
+    Pattern(Regex),
+    #[default]
+    String,
+    Url,
+}
+
+fn is_url(value: &str) -> bool {
+    if value
+        .chars()
+        .any(|char| char.is_whitespace() || char.is_control() || matches!(char, '<' | '>' | '"'))
+    {
+        return false;
+    }
+
+    let end = value.find(['/', '?', '#']).unwrap_or(value.len());
+
+    match value[..end].split_once(':') {
+        Some((scheme, _)) => {
+            let mut chars = scheme.chars();
+
+            chars.next().is_some_and(|char| char.is_ascii_alphabetic())
+                && chars.all(|char| char.is_ascii_alphanumeric() || matches!(char, '+' | '-' | '.'))
+        }
+        None => true,
+    }
+}
+
+impl Type {
+    /// Checks a value, `None` standing for an attribute written without one.
+    ///
+    /// Numbers and booleans are accepted by their own types and by any type
+    /// that accepts them as written, `null` by every type.
+    pub fn accepts(&self, value: Option<&Value>) -> bool {
+        #[rustfmt::skip]
+        let typed = match value {
+            None => return matches!(self, Type::Boolean),
+            Some(Value::Null) => return true,
+            Some(Value::String(value)) => return self.accepts_text(value),
+            Some(Value::Boolean(_)) => matches!(self, Type::Boolean),
+            Some(Value::Float(_)) => matches!(self, Type::Float),
+            Some(Value::Integer(_)) => matches!(self, Type::Float | Type::Integer),
+        };
+
+        typed || value.is_some_and(|value| self.accepts_text(&value.to_string()))
+    }
+
+    fn accepts_text(&self, value: &str) -> bool {
+        match self {
+            Type::Boolean => matches!(value, "true" | "false"),
+            Type::Enum(values) => values.iter().any(|literal| literal == value),
+            Type::Float => value.parse::<f64>().is_ok_and(f64::is_finite),
+            Type::Integer => value.parse::<i64>().is_ok(),
+            Type::Pattern(pattern) => pattern.is_match(value),
+            Type::String => true,
+            Type::Url => is_url(value),
+        }
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Boolean => write!(f, "a boolean"),
+            Type::Enum(values) => write!(f, "one of '{}'", values.join("', '")),
+            Type::Float => write!(f, "a number"),
+            Type::Integer => write!(f, "an integer"),
+            Type::Pattern(pattern) => {
+                let pattern = pattern.as_str();
+                let pattern = pattern
+                    .strip_prefix("^(?:")
+                    .and_then(|pattern| pattern.strip_suffix(")$"))
+                    .unwrap_or(pattern);
+
+                write!(f, "a value matching '{}'", pattern)
+            }
+            Type::String => write!(f, "a string"),
+            Type::Url => write!(f, "a URL"),
+        }
+    }
+}
+
+/// What to do when an attribute is written more than once on the same element.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum Merge {
+    /// Join every value with the separator, e.g. the tokens of `class`.
+    Join(String),
+    #[default]
+    Reject,
+}
+
+/// The declaration of one attribute, either global or owned by an element.
+#[derive(Clone, Debug, Default)]
+pub struct Attribute {
+    pub default: Option<Value>,
+    pub merge: Merge,
+    pub name: String,
+    pub required: bool,
+    pub span: Span,
+    pub value: Type,
+}
+
+impl Attribute {
+    pub fn new<T>(name: T, value: Type) -> Self
+    where
+        T: Into<String>,
+    {
+        let name = name.into();
+
+        Self {
+            name,
+            value,
+            ..Self::default()
+        }
+    }
+}
+
+/// The content model of one element.
+#[derive(Clone, Debug, Default)]
+pub struct Element {
+    pub attributes: Vec<Attribute>,
+    pub children: Vec<String>,
+    pub name: String,
+    pub open: bool,
+    pub required: Vec<String>,
+    pub span: Span,
+    pub text: bool,
+}
+
+impl Element {
+    pub fn new<T>(name: T) -> Self
+    where
+        T: Into<String>,
+    {
+        let name = name.into();
+
+        Self {
+            name,
+            ..Self::default()
+        }
+    }
+
+    pub fn allows(&self, child: &str) -> bool {
+        self.open || self.children.iter().any(|name| name == child)
+    }
+}
+
+/// The vocabulary a document is validated against.
+///
+/// A schema is itself written in Markup-Type:
+///
+/// ```text
+/// root { html }
+///
+/// element | name "ul" {
+///     children { li }
+///     required { li }
+/// }
+///
+/// element | name "li" | text | open
+/// ```
+///
+/// `text` lets the element hold text, `open` allows any declared element as a
+/// child, and `root` lists the elements allowed at the top of the document
+/// (every declared element when it is missing).
+///
+/// Attributes are declared inside an element, or at the top of the schema to
+/// allow them on every element:
+///
+/// ```text
+/// attribute | name "id" | type "pattern" | pattern "[a-z][a-z0-9-]*"
+///
+/// element | name "a" | text {
+///     attribute | name "href" | type "url" | required
+///     attribute | name "target" | type "enum" | values "_self _blank" | default "_self"
+/// }
+/// ```
+///
+/// The types are `string` (the default), `integer`, `float`, `boolean`, `url`,
+/// `enum` and `pattern`. An attribute written twice on one element is an error unless
+/// it is declared with `| merge "join"`, which joins its values with
+/// `| separator` (a space by default).
+#[derive(Clone, Debug, Default)]
+pub struct Schema {
+    elements: HashMap<String, Element>,
+    globals: Vec<Attribute>,
+    roots: Vec<String>,
+}
+
+impl Schema {
+    pub fn element(&self, name: &str) -> Option<&Element> {
+        self.elements.get(name)
+    }
+
+    /// Every attribute `element` accepts, its own declarations first.
+    pub fn attributes<'a>(&'a self, element: &'a Element) -> impl Iterator<Item = &'a Attribute> {
+        element.attributes.iter().chain(
+            self.globals
+                .iter()
+                .filter(|global| element.attributes.iter().all(|own| own.name != global.name)),
+        )
+    }
+
+    pub fn insert(&mut self, element: Element) {
+        self.elements.insert(element.name.clone(), element);
+    }
+
+    /// Finds the declaration of `attribute` on `element`, falling back to the
+    /// global declarations when the element is not declared.
+    pub fn attribute(&self, element: &str, attribute: &str) -> Option<&Attribute> {
+        let matches = |declaration: &&Attribute| declaration.name == attribute;
+
+        match self.element(element) {
+            Some(element) => self.attributes(element).find(matches),
+            None => self.globals.iter().find(matches),
+        }
+    }
+
+    pub fn allows_root(&self, name: &str) -> bool {
+        self.roots.is_empty() || self.roots.iter().any(|root| root == name)
+    }
+}
+
+fn names(element: &tree::Element) -> Vec<String> {
+    #[rustfmt::skip]
+    let names = element.children.iter().filter_map(|node| match node {
+        Node::Element(element) => Some(element.name.clone()),
+        _ => None,
+    }).collect();
+
+    names
+}
+
+fn value(declaration: &tree::Element, name: &str) -> Option<String> {
+    declaration
+        .attribute(name)
+        .and_then(|attribute| attribute.value.as_ref())
+        .map(Value::to_string)
+}
+
+fn load_attribute(declaration: &tree::Element, trace: &mut Vec<Diagnostic>) -> Option<Attribute> {
+    let mut report = |message: &str| {
+        let diagnostic =
+            Diagnostic::new(diagnostic::Kind::Error, message, declaration.span.clone());
+
+        trace.push(diagnostic);
+    };
+
+    let Some(name) = value(declaration, "name") else {
+        report("a schema attribute requires a 'name' attribute");
+
+        return None;
+    };
+
+    #[rustfmt::skip]
+    let kind = match value(declaration, "type").as_deref().unwrap_or("string") {
+        "boolean" => Type::Boolean,
+        "enum" => {
+            let values = value(declaration, "values").unwrap_or_default();
+
+            Type::Enum(values.split_whitespace().map(String::from).collect())
+        },
+        "float" => Type::Float,
+        "integer" => Type::Integer,
+        "pattern" => {
+            let pattern = value(declaration, "pattern").unwrap_or_default();
+
+            match Regex::new(&format!("^(?:{})$", pattern)) {
+                Ok(pattern) => Type::Pattern(pattern),
+                Err(error) => {
+                    report(&format!("the pattern of the attribute '{}' is invalid: {}", name, error));
+
+                    return None;
+                }
+            }
+        },
+        "string" => Type::String,
+        "url" => Type::Url,
+        kind => {
+            report(&format!("unknown attribute type '{}', expected 'string', 'integer', 'float', 'boolean', 'url', 'enum' or 'pattern'", kind));
+
+            return None;
+        }
+    };
+
+    let mut attribute = Attribute::new(name, kind);
+
+    attribute.default = declaration
+        .attribute("default")
+        .and_then(|attribute| attribute.value.clone());
+    attribute.merge = match value(declaration, "merge").as_deref() {
+        Some("join") => {
+            let separator = value(declaration, "separator").unwrap_or_else(|| " ".into());
+
+            Merge::Join(separator)
+        }
+        Some("reject") | None => Merge::Reject,
+        Some(merge) => {
+            report(&format!(
+                "unknown merge policy '{}', expected 'join' or 'reject'",
+                merge
+            ));
+
+            Merge::Reject
+        }
+    };
+    attribute.required = declaration.attribute("required").is_some();
+    attribute.span = declaration.span.clone();
+
+    if let Some(default) = &attribute.default {
+        if !attribute.value.accepts(Some(default)) {
+            report(&format!(
+                "the default value of the attribute '{}' is not {}",
+                attribute.name, attribute.value
+            ));
+        }
+    }
+
+    Some(attribute)
+}
+
+impl Schema {
+    fn load_element(&mut self, declaration: &tree::Element, trace: &mut Vec<Diagnostic>) {
+        let Some(name) = value(declaration, "name") else {
+            let message = "a schema element requires a 'name' attribute";
+            let diagnostic =
+                Diagnostic::new(diagnostic::Kind::Error, message, declaration.span.clone());
+
+            return trace.push(diagnostic);
+        };
+
+        let mut element = Element::new(name);
+
+        element.open = declaration.attribute("open").is_some();
+        element.span = declaration.span.clone();
+        element.text = declaration.attribute("text").is_some();
+
+        for node in &declaration.children {
+            let Node::Element(entry) = node else {
+                continue;
+            };
+
+            #[rustfmt::skip]
+            match entry.name.as_str() {
+                "attribute" => element.attributes.extend(load_attribute(entry, trace)),
+                "children" => element.children.extend(names(entry)),
+                "required" => element.required.extend(names(entry)),
+                _ => {
+                    let message = format!("unexpected '{}' in a schema element, expected 'attribute', 'children' or 'required'", entry.name);
+                    let diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, entry.span.clone());
+
+                    trace.push(diagnostic);
+                }
+            };
+        }
+
+        self.insert(element);
+    }
+
+    pub fn from_document(document: &Document) -> (Self, Vec<Diagnostic>) {
+        let mut schema = Self::new();
+        let mut trace = Vec::new();
+
+        for node in &document.children {
+            let Node::Element(entry) = node else {
+                continue;
+            };
+
+            #[rustfmt::skip]
+            match entry.name.as_str() {
+                "attribute" => schema.globals.extend(load_attribute(entry, &mut trace)),
+                "element" => schema.load_element(entry, &mut trace),
+                "root" => schema.roots.extend(names(entry)),
+                _ => {
+                    let message = format!("unexpected '{}' in a schema, expected 'attribute', 'element' or 'root'", entry.name);
+                    let diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, entry.span.clone());
+
+                    trace.push(diagnostic);
+                }
+            };
+        }
+
+        (schema, trace)
+    }
+
+    /// Parses a schema written in Markup-Type.
+    pub fn parse(source: &Rc<Source>) -> (Self, Vec<Diagnostic>) {
+        let (document, mut trace) = Builder::new(analyzer::analyze(source)).build();
+        let (schema, diagnostics) = Self::from_document(&document);
+
+        trace.extend(diagnostics);
+
+        (schema, trace)
+    }
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self {
+            elements: HashMap::new(),
+            globals: Vec::new(),
+            roots: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analyzer::{source::Source, syntactic::Value};
+
+    use super::{Merge, Schema, Type};
+
+    #[test]
+    fn test() {
+        let schema = Source::string(
+            "schema.mt",
+            "attribute | name \"class\" | merge \"join\" element | name \"meter\" { attribute | name \"value\" | type \"float\" | default 0.5 attribute | name \"max\" | type \"integer\" | default \"high\" attribute | name \"low\" | type \"decimal\" attribute | name \"id\" | type \"pattern\" | pattern \"[a-z\" }",
+        );
+
+        let (schema, diagnostics) = Schema::parse(&schema);
+
+        let messages: Vec<&str> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(
+            messages[0],
+            "the default value of the attribute 'max' is not an integer"
+        );
+        assert_eq!(
+            messages[1],
+            "unknown attribute type 'decimal', expected 'string', 'integer', 'float', 'boolean', 'url', 'enum' or 'pattern'"
+        );
+        assert!(messages[2].starts_with("the pattern of the attribute 'id' is invalid"));
+
+        let value = schema.attribute("meter", "value");
+
+        assert!(value.is_some_and(|value| matches!(value.value, Type::Float)));
+        assert_eq!(
+            value.and_then(|value| value.default.clone()),
+            Some(Value::Float(0.5))
+        );
+
+        assert!(schema
+            .attribute("meter", "max")
+            .is_some_and(|max| matches!(max.value, Type::Integer)));
+        assert!(schema.attribute("meter", "low").is_none());
+        assert!(schema.attribute("meter", "id").is_none());
+
+        assert_eq!(
+            schema.attribute("meter", "class").map(|class| &class.merge),
+            Some(&Merge::Join(" ".into()))
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use std::{io::Read, rc::Rc};
+
+use crate::analyzer::{
+    diagnostic::{self, Diagnostic, Span},
+    filesystem::{Error, FileReader},
+};
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Kind {
+    File,
+    Stdin,
+    String,
+    #[default]
+    Virtual,
+}
+
+/// The full text of one input, shared by every [`Span`] that points into it.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Source {
+    pub kind: Kind,
+    pub name: String,
+    pub text: String,
+}
+
+impl Source {
+    fn decode<R>(kind: Kind, name: &str, reader: FileReader<R>) -> std::io::Result<Decoded>
+    where
+        R: Read,
+    {
+        let mut text = String::new();
+        let mut sequences = Vec::new();
+
+        for item in reader {
+            match item {
+                Ok(char) => text.push(char),
+                Err(Error::Io(error)) => return Err(error),
+                Err(Error::Utf8(sequence)) => {
+                    text.push(char::REPLACEMENT_CHARACTER);
+                    sequences.push(sequence);
+                }
+            }
+        }
+
+        let source = Self::new(kind, name, text);
+
+        #[rustfmt::skip]
+        let trace = sequences.into_iter().map(|sequence| {
+            let span = Span::new(source.clone(), sequence.start, sequence.end);
+
+            Diagnostic::new(diagnostic::Kind::Error, &sequence.to_string(), span)
+        }).collect();
+
+        Ok((source, trace))
+    }
+
+    /// Reads and decodes a file of at most `limit` bytes, reporting every
+    /// invalid UTF-8 sequence.
+    pub fn file(path: &str, limit: Option<usize>) -> std::io::Result<Decoded> {
+        Self::decode(Kind::File, path, FileReader::new(path)?.with_limit(limit))
+    }
+
+    /// Reads and decodes at most `limit` bytes of the standard input, reporting
+    /// every invalid UTF-8 sequence.
+    pub fn stdin(limit: Option<usize>) -> std::io::Result<Decoded> {
+        Self::decode(
+            Kind::Stdin,
+            "<stdin>",
+            FileReader::from_reader(std::io::stdin()).with_limit(limit),
+        )
+    }
+
+    /// Decodes bytes held in memory, reporting every invalid UTF-8 sequence.
+    pub fn bytes(name: &str, bytes: &[u8]) -> std::io::Result<Decoded> {
+        Self::decode(Kind::Virtual, name, FileReader::from_reader(bytes))
+    }
+
+    pub fn string<N, T>(name: N, text: T) -> Rc<Self>
+    where
+        N: Into<String>,
+        T: Into<String>,
+    {
+        Self::new(Kind::String, name, text)
+    }
+
+    pub fn new<N, T>(kind: Kind, name: N, text: T) -> Rc<Self>
+    where
+        N: Into<String>,
+        T: Into<String>,
+    {
+        let name = name.into();
+        let text = text.into();
+
+        Rc::new(Self { kind, name, text })
+    }
+}
+
+impl Source {
+    pub fn chars(self: &Rc<Self>) -> Chars {
+        Chars {
+            offset: 0,
+            source: self.clone(),
+        }
+    }
+
+    /// The URI of the source: a `file://` URI with its absolute path for a
+    /// file, or the name itself when it is already one, as editors name their
+    /// documents. Other sources, such as the standard input, have none.
+    pub fn uri(&self) -> Option<String> {
+        if self.name.starts_with("file://") {
+            return Some(self.name.clone());
+        }
+
+        if self.kind != Kind::File {
+            return None;
+        }
+
+        let path = std::path::absolute(&self.name).unwrap_or_else(|_| self.name.clone().into());
+        let mut uri = String::from("file://");
+
+        for &byte in path.to_string_lossy().as_bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                    uri.push(char::from(byte));
+                }
+                _ => uri.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+
+        Some(uri)
+    }
+
+    /// Returns the text of the zero-based `row`, without its line terminator.
+    pub fn line(&self, row: usize) -> Option<&str> {
+        self.text
+            .split('\n')
+            .nth(row)
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+    }
+}
+
+pub type Decoded = (Rc<Source>, Vec<Diagnostic>);
+
+/// An owning iterator over the characters of a [`Source`].
+#[derive(Clone, Debug)]
+pub struct Chars {
+    offset: usize,
+    source: Rc<Source>,
+}
+
+impl Iterator for Chars {
+    type Item = char;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let char = self.source.text[self.offset..].chars().next()?;
+        self.offset += char.len_utf8();
+
+        Some(char)
+    }
+}
+
+/// Every input loaded during one run, looked up by name.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    /// The most bytes an input can have.
+    limit: Option<usize>,
+    sources: Vec<Rc<Source>>,
+}
+
+impl SourceMap {
+    pub fn get(&self, name: &str) -> Option<&Rc<Source>> {
+        self.sources.iter().find(|source| source.name == name)
+    }
+
+    pub fn insert(&mut self, source: Rc<Source>) -> Rc<Source> {
+        self.sources.retain(|other| other.name != source.name);
+        self.sources.push(source.clone());
+
+        source
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Rc<Source>> {
+        self.sources.iter()
+    }
+
+    /// Loads `path` from the filesystem, or the standard input when it is `-`.
+    pub fn load(&mut self, path: &str) -> std::io::Result<Decoded> {
+        #[rustfmt::skip]
+        let (source, trace) =
+        if path == "-" {
+            Source::stdin(self.limit)?
+        } else {
+            Source::file(path, self.limit)?
+        };
+
+        Ok((self.insert(source), trace))
+    }
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self {
+            limit: None,
+            sources: Vec::new(),
+        }
+    }
+
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..Self::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Kind, Source, SourceMap};
+
+    #[test]
+    fn test() -> std::io::Result<()> {
+        let source = Source::string("test.mt", "p\r\n\u{e9}t\u{e9} \u{1f600}\r\nlast");
+
+        assert_eq!(source.line(0), Some("p"));
+        assert_eq!(source.line(1), Some("\u{e9}t\u{e9} \u{1f600}"));
+        assert_eq!(source.line(2), Some("last"));
+        assert_eq!(source.line(3), None);
+
+        let chars: String = source.chars().collect();
+
+        assert_eq!(chars, source.text);
+        assert_eq!(source.chars().count(), 14);
+
+        let (source, trace) = Source::bytes("bytes.mt", b"a\n\xc3\xa9\xff\nb")?;
+
+        assert_eq!(source.kind, Kind::Virtual);
+        assert_eq!(source.text, "a\n\u{e9}\u{fffd}\nb");
+        assert_eq!(source.line(2), Some("b"));
+
+        #[rustfmt::skip]
+        let spans: Vec<_> = trace.iter().map(|diagnostic| (diagnostic.span.start, diagnostic.span.end)).collect();
+
+        assert_eq!(spans.len(), 1);
+        assert_eq!((spans[0].0.row, spans[0].0.col, spans[0].0.byte), (1, 1, 4));
+
+        let mut map = SourceMap::new();
+
+        map.insert(Source::string("a.mt", "old"));
+        map.insert(Source::string("b.mt", "b"));
+        map.insert(Source::string("a.mt", "new"));
+
+        #[rustfmt::skip]
+        let names: Vec<&str> = map.iter().map(|source| source.name.as_str()).collect();
+
+        assert_eq!(names, ["b.mt", "a.mt"]);
+        assert_eq!(
+            map.get("a.mt").map(|source| source.text.as_str()),
+            Some("new")
+        );
+        assert!(map.get("c.mt").is_none());
+
+        Ok(())
+    }
+}
@@ -1,42 +1,202 @@
-use std::iter::Peekable;
+use std::{collections::VecDeque, iter::Peekable, rc::Rc};
 
 use iterator_stage::Processor;
 
 use crate::analyzer::{
-    diagnostic::{self, Span},
+    diagnostic::{self, Diagnostic, Span},
     lexical::{self, Token},
 };
 
 use super::{
-    Attribute, Borrow, Borrowable, Comment, Element, Kind, Node, ProcessingInstruction, Result, Text
+    Attribute, Borrow, Borrowable, Comment, Element, Include, Kind, Node, ProcessingInstruction, Result, Text, Value
 };
 
+/// The attribute value a lexeme stands for, if it stands for one.
+fn attribute_value(lexeme: &lexical::Lexeme) -> Option<Value> {
+    match lexeme {
+        lexical::Lexeme::Boolean(value) => Some(Value::Boolean(*value)),
+        lexical::Lexeme::Float(value) => Some(Value::Float(*value)),
+        lexical::Lexeme::Integer(value) => Some(Value::Integer(*value)),
+        lexical::Lexeme::Literal(value) => Some(Value::String(value.clone())),
+        lexical::Lexeme::Null => Some(Value::Null),
+        lexical::Lexeme::Comment(_) | lexical::Lexeme::Identifier(_) => None,
+    }
+}
+
+/// Whether a literal is written raw, as `r"..."`, or between triple quotes,
+/// so that its text is taken as it is rather than searched for variables.
+fn is_verbatim(span: &Span) -> bool {
+    let text = span.text();
+
+    text.starts_with('r') || text.starts_with("\"\"\"") || text.starts_with("'''")
+}
+
 pub struct Analyzer<I>
 where
     I: Iterator<Item = Token>,
 {
+    braces: Vec<Span>,
+    closed: Option<Span>,
     depth: u16,
+    end: Option<Span>,
+    finished: bool,
+    /// The deepest a block can be nested.
+    limit: u16,
+    trace: VecDeque<Diagnostic>,
+    trivia: Vec<Token>,
     upstream: Peekable<I>,
 }
 
+impl<I> Analyzer<I>
+where
+    I: Iterator<Item = Token>,
+{
+    /// Takes the next token, keeping the problems found while reading it.
+    fn advance(&mut self) -> Option<Token> {
+        self.advance_if(|_| true)
+    }
+
+    fn advance_if<F>(&mut self, predicate: F) -> Option<Token>
+    where
+        F: FnOnce(&Token) -> bool,
+    {
+        let mut token = self.upstream.next_if(predicate)?;
+        self.trace.extend(token.trace.drain(..));
+
+        Some(token)
+    }
+
+    /// Moves the trivia ahead into the buffer attached to the next node.
+    fn skip_trivia(&mut self) {
+        while let Some(token) =
+            self.advance_if(|token| matches!(token.kind, lexical::Kind::Trivia(_)))
+        {
+            self.trivia.push(token);
+        }
+    }
+
+    /// Takes the trivia and delimiters left after the last node.
+    pub fn take_trivia(&mut self) -> Vec<Token> {
+        std::mem::take(&mut self.trivia)
+    }
+}
+
 impl<I> Analyzer<I>
 where
     I: Iterator<Item = Token>,
 {
     fn parse_delimiter(&mut self, kind: lexical::Delimiter) -> Option<Result> {
-        self.upstream.next().unwrap();
+        let token = self.advance()?;
+        let span = token.span.clone();
+
+        self.trivia.push(token);
 
         match kind {
             lexical::Delimiter::LeftBrace => {
-                self.depth += 1;
+                self.braces.push(span.clone());
+
+                let Some(depth) = self.depth.checked_add(1) else {
+                    return Some(self.parse_nested_brace(span));
+                };
+
+                if depth > self.limit {
+                    return Some(self.parse_deep_brace(span));
+                }
+
+                self.depth = depth;
             }
             lexical::Delimiter::RightBrace => {
-                self.depth -= 1;
+                if let Some(open) = self.braces.pop() {
+                    self.closed = Some(open);
+                    self.depth = u16::try_from(self.braces.len()).unwrap_or(u16::MAX);
+
+                    return None;
+                }
+
+                return Some(self.parse_unmatched_brace(span));
             }
             lexical::Delimiter::Semicolon => {}
         };
 
-        self.next()
+        None
+    }
+
+    /// Reports a block nested deeper than a depth can count; its content is
+    /// kept at the deepest depth.
+    fn parse_nested_brace(&mut self, span: Span) -> Result {
+        let message = format!("blocks cannot be nested more than {} levels deep", u16::MAX);
+
+        let diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, span)
+            .with_primary_label("this block is too deep");
+
+        Result::from(diagnostic)
+    }
+
+    /// Stops the nodes at a block nested deeper than the limit, before the
+    /// braces of a hostile input pile up without any node to bound them.
+    fn parse_deep_brace(&mut self, span: Span) -> Result {
+        let message = format!(
+            "this block is nested deeper than the limit of {} levels",
+            self.limit
+        );
+
+        self.finished = true;
+
+        let diagnostic = Diagnostic::new(diagnostic::Kind::Critical, &message, span)
+            .with_note("the analysis stopped here");
+
+        Result::from(diagnostic)
+    }
+
+    /// Reports a `}` that closes no block and skips what follows it up to the
+    /// next brace or semicolon, where parsing resumes.
+    fn parse_unmatched_brace(&mut self, span: Span) -> Result {
+        let message = "this brace closes no block";
+
+        let mut diagnostic = Diagnostic::new(diagnostic::Kind::Error, message, span)
+            .with_primary_label("unmatched closing brace");
+
+        if let Some(open) = &self.closed {
+            let message = "the last block, opened here, is already closed";
+            diagnostic = diagnostic.with_label(open.clone(), message);
+        } else {
+            diagnostic = diagnostic.with_help("remove this brace or open a block before it");
+        }
+
+        while let Some(token) =
+            self.advance_if(|token| !matches!(token.kind, lexical::Kind::Delimiter(_)))
+        {
+            self.trivia.push(token);
+        }
+
+        Result::from(diagnostic)
+    }
+
+    /// Reports, once the input ends, the outermost block that was never
+    /// closed.
+    fn parse_unclosed_brace(&mut self) -> Option<Result> {
+        if self.braces.is_empty() {
+            return None;
+        }
+
+        let open = self.braces.remove(0);
+        let message = "this block is never closed";
+
+        let mut diagnostic = Diagnostic::new(diagnostic::Kind::Error, message, open)
+            .with_primary_label("unclosed opening brace");
+
+        let end = self.trivia.last().map(|token| &token.span);
+        let end = match (end, &self.end) {
+            (Some(end), Some(last)) if last.end.abs > end.end.abs => Some(last),
+            (None, last) => last.as_ref(),
+            (end, _) => end,
+        };
+
+        if let Some(end) = end {
+            diagnostic = diagnostic.with_label(end.clone(), "the input ends here");
+        }
+
+        Some(Result::from(diagnostic))
     }
 }
 
@@ -44,10 +204,17 @@ impl<I> Analyzer<I>
 where
     I: Iterator<Item = Token>,
 {
+    /// A comment starting with exactly `##` documents the element after it. A
+    /// file that used `##` for plain comments keeps them plain with `###`, and
+    /// one that documents nothing is warned about by the tree builder.
     fn parse_comment_lexeme(&mut self, value: String) -> Option<Result> {
-        let token = self.upstream.next().unwrap();
+        let token = self.advance()?;
 
-        let data = Comment::new(value);
+        #[rustfmt::skip]
+        let data = match value.strip_prefix("##").filter(|doc| !doc.starts_with('#')) {
+            Some(doc) => Comment::doc(doc.strip_prefix(' ').unwrap_or(doc).trim_end().to_string()),
+            None => Comment::new(value),
+        };
 
         let kind = Kind::from(data);
         let node = Node::new(self.depth, kind, token.span);
@@ -56,11 +223,13 @@ where
     }
 
     fn parse_identifier_lexeme(&mut self, value: String) -> Option<Result> {
-        let namet = self.upstream.next().unwrap();
+        let namet = self.advance()?;
+
+        self.skip_trivia();
 
         if let Some(item) = self.upstream.peek() {
             if let lexical::Kind::Operator(lexical::Operator::Borrow) = item.kind {
-                let item = self.upstream.next().unwrap();
+                let item = self.advance()?;
 
                 let data = Borrow::new(value);
                 let span = Span::new(namet.span.source, namet.span.start, item.span.end);
@@ -74,7 +243,7 @@ where
             }
 
             if let lexical::Kind::Operator(lexical::Operator::Borrowable) = item.kind {
-                let item = self.upstream.next().unwrap();
+                let item = self.advance()?;
 
                 let data = Borrowable::new(value);
                 let span = Span::new(namet.span.source, namet.span.start, item.span.end);
@@ -97,9 +266,44 @@ where
     }
 
     fn parse_literal_lexeme(&mut self, value: String) -> Option<Result> {
-        let item = self.upstream.next().unwrap();
+        let item = self.advance()?;
+
+        self.skip_trivia();
+
+        if let Some(operator) = self.advance_if(|token| {
+            matches!(
+                token.kind,
+                lexical::Kind::Operator(lexical::Operator::Borrow)
+            )
+        }) {
+            let data = Include::new(value);
+            let span = Span::new(item.span.source, item.span.start, operator.span.end);
+
+            let pi = ProcessingInstruction::from(data);
+
+            let kind = Kind::from(pi);
+            let node = Node::new(self.depth, kind, span);
+
+            return Some(Result::from(node));
+        }
+
+        let data = match is_verbatim(&item.span) {
+            true => Text::verbatim(value),
+            false => Text::new(value),
+        };
 
-        let data = Text::new(value);
+        let kind = Kind::new(data);
+
+        let node = Node::new(self.depth, kind, item.span);
+
+        Some(Result::from(node))
+    }
+
+    /// Numbers, booleans and `null` written as content are text, as written.
+    fn parse_value_lexeme(&mut self) -> Option<Result> {
+        let item = self.advance()?;
+
+        let data = Text::new(item.span.text().to_string());
         let kind = Kind::new(data);
 
         let node = Node::new(self.depth, kind, item.span);
@@ -112,6 +316,10 @@ where
             lexical::Lexeme::Comment(value) => self.parse_comment_lexeme(value),
             lexical::Lexeme::Identifier(value) => self.parse_identifier_lexeme(value),
             lexical::Lexeme::Literal(value) => self.parse_literal_lexeme(value),
+            lexical::Lexeme::Boolean(_)
+            | lexical::Lexeme::Float(_)
+            | lexical::Lexeme::Integer(_)
+            | lexical::Lexeme::Null => self.parse_value_lexeme(),
         }
     }
 }
@@ -121,25 +329,38 @@ where
     I: Iterator<Item = Token>,
 {
     fn parse_pipe_operator(&mut self, token: lexical::Token) -> Option<Result> {
-        if let Some(namet) = self.upstream.next() {
+        self.skip_trivia();
+
+        #[rustfmt::skip]
+        let namet = self.advance_if(|namet| matches!(namet.kind, lexical::Kind::Lexeme(lexical::Lexeme::Identifier(_) | lexical::Lexeme::Literal(_))));
+
+        if let Some(namet) = namet {
             if let lexical::Kind::Lexeme(
                 lexical::Lexeme::Identifier(name) | lexical::Lexeme::Literal(name),
             ) = namet.kind
             {
-                if let Some(valuet) = self.upstream.peek() {
-                    if let lexical::Kind::Lexeme(lexical::Lexeme::Literal(value)) =
-                        valuet.kind.clone()
-                    {
-                        let valuet = self.upstream.next().unwrap();
+                self.skip_trivia();
 
-                        let data = Attribute::new(name, Some(value));
-                        let span = Span::new(token.span.source, token.span.start, valuet.span.end);
+                #[rustfmt::skip]
+                let value = self.upstream.peek().and_then(|valuet| match &valuet.kind {
+                    lexical::Kind::Lexeme(lexeme) => attribute_value(lexeme),
+                    _ => None,
+                });
 
-                        let kind = Kind::from(data);
-                        let node = Node::new(self.depth, kind, span);
+                if let Some(value) = value {
+                    let valuet = self.advance()?;
 
-                        return Some(Result::from(node));
-                    }
+                    let data = match is_verbatim(&valuet.span) {
+                        true => Attribute::verbatim(name, Some(value)),
+                        false => Attribute::new(name, Some(value)),
+                    };
+
+                    let span = Span::new(token.span.source, token.span.start, valuet.span.end);
+
+                    let kind = Kind::from(data);
+                    let node = Node::new(self.depth, kind, span);
+
+                    return Some(Result::from(node));
                 }
 
                 let data = Attribute::new(name, None);
@@ -152,6 +373,8 @@ where
             }
         }
 
+        self.trivia.insert(0, token.clone());
+
         let message = "an identifier was expected after the property declaration";
 
         let diagnostic = diagnostic::Diagnostic::new(diagnostic::Kind::Error, &message, token.span);
@@ -161,10 +384,12 @@ where
     }
 
     fn parse_operator(&mut self, kind: lexical::Operator) -> Option<Result> {
-        let token = self.upstream.next().unwrap();
-
         match kind {
-            lexical::Operator::Pipe => self.parse_pipe_operator(token),
+            lexical::Operator::Pipe => {
+                let token = self.advance()?;
+
+                self.parse_pipe_operator(token)
+            }
             _ => self.parse_unknown(),
         }
     }
@@ -175,7 +400,9 @@ where
     I: Iterator<Item = Token>,
 {
     pub fn parse_unknown(&mut self) -> Option<Result> {
-        let token = self.upstream.next().unwrap();
+        let token = self.advance()?;
+
+        self.trivia.push(token.clone());
 
         let message = "an unknown token was unexpectedly found";
 
@@ -192,16 +419,67 @@ where
 {
     type Item = Result;
 
+    /// Parses the next node, attaching to it the trivia and delimiters found
+    /// before it; those found inside its span are covered by the span itself.
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(token) = self.upstream.peek() {
-            match token.kind.clone() {
-                lexical::Kind::Delimiter(delimiter) => self.parse_delimiter(delimiter),
-                lexical::Kind::Lexeme(lexeme) => self.parse_lexeme(lexeme),
-                lexical::Kind::Operator(operator) => self.parse_operator(operator),
-                _ => self.parse_unknown(),
+        loop {
+            if self.finished {
+                return None;
             }
-        } else {
-            None
+
+            if let Some(diagnostic) = self.trace.pop_front() {
+                return Some(Result::from(diagnostic));
+            }
+
+            let Some(token) = self.upstream.peek() else {
+                return self.parse_unclosed_brace();
+            };
+
+            let kind = token.kind.clone();
+
+            if let lexical::Kind::Delimiter(delimiter) = kind {
+                if let Some(result) = self.parse_delimiter(delimiter) {
+                    return Some(result);
+                }
+
+                continue;
+            }
+
+            if let lexical::Kind::Trivia(_) = kind {
+                self.skip_trivia();
+
+                continue;
+            }
+
+            let leading = self.take_trivia();
+
+            #[rustfmt::skip]
+            let mut result = match kind {
+                lexical::Kind::Lexeme(lexeme) => self.parse_lexeme(lexeme)?,
+                lexical::Kind::Operator(operator) => self.parse_operator(operator)?,
+                _ => self.parse_unknown()?,
+            };
+
+            let mut trivia = leading;
+
+            match &mut result {
+                Result::Value(node) => {
+                    let end = node.span.end.abs;
+                    self.end = Some(node.span.clone());
+
+                    if let Some(node) = Rc::get_mut(node) {
+                        node.trivia = trivia;
+                    }
+
+                    self.trivia.retain(|token| token.span.start.abs >= end);
+                }
+                Result::Diagnostic(_) => {
+                    trivia.append(&mut self.trivia);
+                    self.trivia = trivia;
+                }
+            }
+
+            return Some(result);
         }
     }
 }
@@ -212,9 +490,28 @@ where
 {
     pub fn new(upstream: I) -> Self {
         let upstream = upstream.peekable();
+        let braces = Vec::new();
         let depth = 0;
+        let trivia = Vec::new();
+
+        Self {
+            braces,
+            closed: None,
+            depth,
+            end: None,
+            finished: false,
+            limit: u16::MAX,
+            trace: VecDeque::new(),
+            trivia,
+            upstream,
+        }
+    }
 
-        Self { depth, upstream }
+    /// Stops the nodes with a critical diagnostic at the first block nested
+    /// deeper than `limit` levels.
+    pub fn with_limit(mut self, limit: u16) -> Self {
+        self.limit = limit;
+        self
     }
 }
 
@@ -226,3 +523,51 @@ where
         Self::new(upstream)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::analyzer::{
+        lexical::tokenizer,
+        source::Source,
+        syntactic::{Kind, Result},
+    };
+
+    use super::Analyzer;
+
+    #[test]
+    fn test() {
+        let source = Source::string("test.mt", "a { b } } c | d; e { f } g {\n  h\n");
+        let tokens = tokenizer::Analyzer::new(source.clone(), source.chars());
+
+        let results: Vec<Result> = Analyzer::new(tokens).collect();
+
+        #[rustfmt::skip]
+        let diagnostics: Vec<_> = results.iter().filter_map(|result| match result {
+            Result::Diagnostic(diagnostic) => Some(diagnostic),
+            Result::Value(_) => None,
+        }).collect();
+
+        #[rustfmt::skip]
+        let elements: Vec<_> = results.iter().filter_map(|result| match result {
+            Result::Value(node) => match &node.kind {
+                Kind::Element(element) => Some((element.name.as_str(), node.depth)),
+                _ => None,
+            },
+            Result::Diagnostic(_) => None,
+        }).collect();
+
+        assert_eq!(
+            elements,
+            [("a", 0), ("b", 1), ("e", 0), ("f", 1), ("g", 0), ("h", 1)]
+        );
+        assert_eq!(diagnostics.len(), 2);
+
+        assert_eq!(diagnostics[0].message, "this brace closes no block");
+        assert_eq!(diagnostics[0].span.start.abs, 8);
+        assert_eq!(diagnostics[0].labels[0].span.start.abs, 2);
+
+        assert_eq!(diagnostics[1].message, "this block is never closed");
+        assert_eq!(diagnostics[1].span.start.abs, 27);
+        assert_eq!(diagnostics[1].labels[0].span.text(), "h");
+    }
+}
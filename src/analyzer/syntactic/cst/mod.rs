@@ -0,0 +1,132 @@
+use std::rc::Rc;
+
+use crate::analyzer::{
+    diagnostic::Diagnostic,
+    lexical::{tokenizer, Token},
+    source::Source,
+};
+
+use super::{converter, Result};
+
+/// A node of the [`SyntaxTree`] with the nodes written inside its block.
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub children: Vec<Node>,
+    pub value: Rc<super::Node>,
+}
+
+impl Node {
+    fn write(&self, output: &mut String) {
+        for token in &self.value.trivia {
+            output.push_str(token.span.text());
+        }
+
+        output.push_str(self.value.span.text());
+
+        for child in &self.children {
+            child.write(output);
+        }
+    }
+}
+
+/// A lossless concrete syntax tree.
+///
+/// Every node keeps the whitespace, newlines and delimiters written before it
+/// as trivia, and the tree keeps the ones after the last node, so the source
+/// can be rebuilt byte for byte with [`SyntaxTree::text`]. A node is a child of
+/// the closest preceding node at a lower depth.
+#[derive(Clone, Debug, Default)]
+pub struct SyntaxTree {
+    pub children: Vec<Node>,
+    pub trailing: Vec<Token>,
+}
+
+impl SyntaxTree {
+    /// Parses `source` with a lossless tokenizer, returning the tree and the
+    /// syntax errors found on the way.
+    pub fn parse(source: &Rc<Source>) -> (Self, Vec<Diagnostic>) {
+        let tokens = tokenizer::Analyzer::lossless(source.clone(), source.chars());
+        let mut converter = converter::Analyzer::new(tokens);
+
+        let mut tree = Self::default();
+        let mut stack: Vec<Node> = Vec::new();
+        let mut trace = Vec::new();
+
+        let close = |stack: &mut Vec<Node>, tree: &mut Self| {
+            if let Some(node) = stack.pop() {
+                #[rustfmt::skip]
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => tree.children.push(node),
+                };
+            }
+        };
+
+        for result in converter.by_ref() {
+            let value = match result {
+                Result::Diagnostic(diagnostic) => {
+                    trace.push(diagnostic);
+
+                    continue;
+                }
+                Result::Value(value) => value,
+            };
+
+            while stack
+                .last()
+                .is_some_and(|node| node.value.depth >= value.depth)
+            {
+                close(&mut stack, &mut tree);
+            }
+
+            stack.push(Node {
+                children: Vec::new(),
+                value,
+            });
+        }
+
+        while !stack.is_empty() {
+            close(&mut stack, &mut tree);
+        }
+
+        tree.trailing = converter.take_trivia();
+
+        (tree, trace)
+    }
+
+    /// Rebuilds the source text the tree was parsed from.
+    pub fn text(&self) -> String {
+        let mut output = String::new();
+
+        for child in &self.children {
+            child.write(&mut output);
+        }
+
+        for token in &self.trailing {
+            output.push_str(token.span.text());
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analyzer::source::Source;
+
+    use super::SyntaxTree;
+
+    #[test]
+    fn test() {
+        let text = "# é\r\nhtml{\n\tbody | class  'a' ;\n  nav  <-  | ? \"x\"\n\n  }  \n";
+        let source = Source::string("test.mt", text);
+
+        let (tree, diagnostics) = SyntaxTree::parse(&source);
+
+        assert_eq!(tree.text(), text);
+        assert_eq!(diagnostics.len(), 2);
+
+        assert_eq!(tree.children.len(), 2);
+        assert_eq!(tree.children[1].children.len(), 4);
+    }
+}
@@ -1,19 +1,137 @@
-use std::{collections::VecDeque, iter::Peekable};
+use std::{collections::VecDeque, iter::Peekable, rc::Rc};
 
 use iterator_stage::Processor;
 
-use crate::analyzer::diagnostic::Diagnostic;
+use crate::analyzer::diagnostic::{self, Diagnostic, Span};
 
-use super::Result;
+use super::{Attribute, Element, Kind, Node, Result, Value};
 
+/// Rewrites the concise forms of the language into the nodes they stand for,
+/// such as `div.card.wide#main` into a `div` element with its `class` and
+/// `id` attributes.
 pub struct Analyzer<I>
 where
     I: Iterator<Item = Result>,
 {
+    pending: VecDeque<Result>,
     trace: VecDeque<Diagnostic>,
     upstream: Peekable<I>,
 }
 
+/// The part of `span` from the byte `start` to the byte `end` of its text,
+/// which stays on a single line.
+fn slice(span: &Span, start: usize, end: usize) -> Span {
+    let mut from = span.start;
+    let mut to = span.start;
+
+    for (position, offset) in [(&mut from, start), (&mut to, end)] {
+        position.abs += offset;
+        position.byte += offset;
+        position.col += offset;
+    }
+
+    Span::new(span.source.clone(), from, to)
+}
+
+impl<I> Analyzer<I>
+where
+    I: Iterator<Item = Result>,
+{
+    fn error(&mut self, message: &str, span: Span) {
+        let diagnostic = Diagnostic::new(diagnostic::Kind::Error, message, span);
+
+        self.trace.push_back(diagnostic);
+    }
+
+    /// Splits an element name written as a selector into the element and the
+    /// attributes that follow it, every one spanning its part of the name.
+    fn desugar_selector(&mut self, node: &Node, name: &str, index: usize) {
+        let mut classes = Vec::new();
+        let mut class: Option<Span> = None;
+        let mut id: Option<(&str, Span)> = None;
+
+        let mut offset = index;
+
+        while let Some(marker) = name[offset..].chars().next() {
+            #[rustfmt::skip]
+            let end = name[offset + 1..].find(['.', '#']).map_or(name.len(), |end| offset + 1 + end);
+
+            let value = &name[offset + 1..end];
+            let span = slice(&node.span, offset, end);
+
+            offset = end;
+
+            let what = if marker == '#' { "an id" } else { "a class" };
+
+            if value.is_empty() {
+                let message = format!("`{}` must be followed by {} name", marker, what);
+
+                self.error(&message, span);
+            } else if marker == '.' {
+                classes.push(value);
+
+                #[rustfmt::skip]
+                let joined = class.take().map_or(span.clone(), |class| Span::new(span.source.clone(), class.start, span.end));
+
+                class = Some(joined);
+            } else if let Some((_, first)) = &id {
+                let diagnostic = Diagnostic::new(
+                    diagnostic::Kind::Error,
+                    "an element can only have one id",
+                    span,
+                )
+                .with_label(first.clone(), "the id is first given here");
+
+                self.trace.push_back(diagnostic);
+            } else {
+                id = Some((value, span));
+            }
+        }
+
+        let element = Node {
+            kind: Kind::from(Element::new(name[..index].to_string())),
+            span: slice(&node.span, 0, index),
+            ..node.clone()
+        };
+
+        self.pending.push_back(Result::from(element));
+
+        #[rustfmt::skip]
+        let attributes = [
+            id.map(|(value, span)| ("id", value.to_string(), span)),
+            class.map(|span| ("class", classes.join(" "), span)),
+        ];
+
+        for (name, value, span) in attributes.into_iter().flatten() {
+            let data = Attribute::new(name.to_string(), Some(Value::String(value)));
+            let node = Node::new(node.depth, data, span);
+
+            self.pending.push_back(Result::from(node));
+        }
+    }
+
+    fn desugar(&mut self, result: Result) -> Result {
+        let Result::Value(node) = &result else {
+            return result;
+        };
+
+        let Kind::Element(element) = &node.kind else {
+            return result;
+        };
+
+        let Some(index) = element.name.find(['.', '#']) else {
+            return result;
+        };
+
+        let name = element.name.clone();
+        let node = Rc::clone(node);
+
+        self.desugar_selector(&node, &name, index);
+
+        self.pending.pop_front().unwrap_or(result)
+    }
+}
+
 impl<I> Iterator for Analyzer<I>
 where
     I: Iterator<Item = Result>,
@@ -25,7 +143,13 @@ where
             return Some(Result::from(diagnostic));
         }
 
-        self.upstream.next()
+        if let Some(result) = self.pending.pop_front() {
+            return Some(result);
+        }
+
+        let result = self.upstream.next()?;
+
+        Some(self.desugar(result))
     }
 }
 
@@ -35,9 +159,14 @@ where
 {
     pub fn new(upstream: I) -> Self {
         let upstream = upstream.peekable();
+        let pending = VecDeque::new();
         let trace = VecDeque::new();
 
-        Self { trace, upstream }
+        Self {
+            pending,
+            trace,
+            upstream,
+        }
     }
 }
 
@@ -49,3 +178,63 @@ where
         Self::new(upstream)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::analyzer::{
+        lexical::tokenizer,
+        source::Source,
+        syntactic::{converter, Kind, Result, Value},
+    };
+
+    use super::Analyzer;
+
+    fn run(text: &str) -> Vec<Result> {
+        let source = Source::string("test.mt", text);
+        let tokens = tokenizer::Analyzer::new(source.clone(), source.chars());
+
+        Analyzer::new(converter::Analyzer::new(tokens)).collect()
+    }
+
+    #[test]
+    fn test() {
+        let results = run("div.card.wide#main { p }");
+
+        #[rustfmt::skip]
+        let nodes: Vec<(String, &str)> = results.iter().filter_map(|result| match result {
+            Result::Value(node) => match &node.kind {
+                Kind::Attribute(attribute) => Some((format!("{} {}", attribute.name, attribute.value.as_ref().map(Value::to_string).unwrap_or_default()), node.span.text())),
+                Kind::Element(element) => Some((element.name.clone(), node.span.text())),
+                _ => None,
+            },
+            Result::Diagnostic(_) => None,
+        }).collect();
+
+        assert_eq!(
+            nodes,
+            [
+                ("div".to_string(), "div"),
+                ("id main".to_string(), "#main"),
+                ("class card wide".to_string(), ".card.wide"),
+                ("p".to_string(), "p"),
+            ]
+        );
+
+        let results = run("p#a#b. p.# note");
+
+        #[rustfmt::skip]
+        let messages: Vec<(&str, &str)> = results.iter().filter_map(|result| match result {
+            Result::Diagnostic(diagnostic) => Some((diagnostic.message.as_str(), diagnostic.span.text())),
+            Result::Value(_) => None,
+        }).collect();
+
+        assert_eq!(
+            messages,
+            [
+                ("an element can only have one id", "#b"),
+                ("`.` must be followed by a class name", "."),
+                ("`.` must be followed by a class name", "."),
+            ]
+        );
+    }
+}
@@ -1,19 +1,995 @@
-use std::{collections::VecDeque, iter::Peekable};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    iter::Peekable,
+    rc::Rc,
+};
 
 use iterator_stage::Processor;
 
-use crate::analyzer::diagnostic::Diagnostic;
+use crate::analyzer::diagnostic::{self, Diagnostic, Span};
 
-use super::Result;
+use super::{limits::Limits, Attribute, Kind, Node, ProcessingInstruction, Result, Text, Value};
 
+/// The element that declares a component.
+const DECLARATION: &str = "component";
+
+/// The element that binds the variables written as its attributes.
+const BINDING: &str = "let";
+
+/// The slot that takes the children a call site writes outside any slot.
+const CHILDREN: &str = "children";
+
+#[derive(Debug)]
+struct Parameter {
+    default: Option<Value>,
+    name: String,
+    span: Span,
+}
+
+/// A place in the body of a component, written `header <-`, that call sites
+/// fill with `header -> { ... }`.
+///
+/// A slot is required unless it holds the content used when it is not filled,
+/// as in `footer <- { p { "none" } }`. A required slot the call site does not
+/// fill is left as a borrow when the document has a borrowable of its name in
+/// scope, and reported otherwise.
+#[derive(Debug)]
+struct Slot {
+    name: String,
+    required: bool,
+    span: Span,
+}
+
+/// A component declared with `component | name "Card" | title | level 2 { ... }`.
+///
+/// Every attribute but `name` is a parameter, required unless it has a
+/// default value, and the children are the body, at depths relative to it.
+#[derive(Debug)]
+struct Component {
+    body: Rc<[Rc<Node>]>,
+    name: String,
+    parameters: Vec<Parameter>,
+    slots: Vec<Slot>,
+    span: Span,
+}
+
+/// The nodes a call site writes for a slot.
+struct Fill {
+    /// Whether the nodes are children written outside any slot.
+    loose: bool,
+    nodes: Vec<Rc<Node>>,
+    span: Span,
+}
+
+/// An element naming a component, with its arguments and children.
+struct Use {
+    arguments: Vec<Rc<Node>>,
+    children: Vec<Rc<Node>>,
+    depth: u16,
+    name: String,
+    span: Span,
+}
+
+/// Where the nodes of a [`Frame`] come from.
+enum Origin {
+    /// The body of a component, with the slots its call site fills.
+    Component {
+        component: Rc<Component>,
+        fills: HashMap<String, Rc<[Rc<Node>]>>,
+        span: Span,
+    },
+    /// The content of a slot, written inside the frames below this index.
+    Slot(usize),
+}
+
+/// A body or slot content being inlined.
+struct Frame {
+    depth: u16,
+    index: usize,
+    nodes: Rc<[Rc<Node>]>,
+    origin: Origin,
+    /// The scope the nodes are resolved in, if they were not resolved where
+    /// they were written.
+    scope: Option<usize>,
+}
+
+/// Whether an element name refers to a component rather than to an element.
+fn is_component(name: &str) -> bool {
+    name.starts_with(|char: char| char.is_ascii_uppercase())
+}
+
+/// Whether an element name is written in capitals only, as in `HTML`, which
+/// makes it an element unless a component is declared with it.
+fn is_capitalized(name: &str) -> bool {
+    !name.chars().any(char::is_lowercase)
+}
+
+/// Whether an element named `name` uses a component, declared or not.
+fn is_use(components: &HashMap<String, Rc<Component>>, name: &str) -> bool {
+    components.contains_key(name) || is_component(name) && !is_capitalized(name)
+}
+
+/// The part of `span` that starts `offset` bytes into its text and is
+/// `length` bytes long.
+fn locate(span: &Span, offset: usize, length: usize) -> Span {
+    let text = span.text();
+
+    let Some(prefix) = text.get(..offset + length) else {
+        return span.clone();
+    };
+
+    let mut start = span.start;
+    let mut end = span.start;
+
+    for (index, char) in prefix.char_indices() {
+        if index == offset {
+            start = end;
+        }
+
+        end.abs += 1;
+        end.byte += char.len_utf8();
+
+        match char {
+            '\n' => {
+                end.col = 0;
+                end.row += 1;
+            }
+            _ => end.col += 1,
+        }
+    }
+
+    Span::new(span.source.clone(), start, end)
+}
+
+/// Whether `name` can be written inside braces to refer to a variable.
+fn is_variable(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|char| char.is_alphanumeric() || char == '-' || char == '_')
+}
+
+/// The node moved `depth` levels deeper.
+fn rebase(node: &Node, depth: u16) -> Node {
+    Node {
+        depth: node.depth.saturating_add(depth),
+        ..node.clone()
+    }
+}
+
+/// A variable bound with `let | name "value"`, seen by the nodes written after
+/// the `let` at its depth or deeper.
+struct Binding {
+    depth: u16,
+    name: String,
+    span: Span,
+    used: bool,
+    value: Value,
+}
+
+impl Binding {
+    fn unused(&self) -> Option<Diagnostic> {
+        if self.used {
+            return None;
+        }
+
+        let message = format!("the variable '{}' is never used", self.name);
+
+        #[rustfmt::skip]
+        let span = self.span.text().find(&self.name).map_or(self.span.clone(), |offset| locate(&self.span, offset, self.name.len()));
+
+        let diagnostic = Diagnostic::new(diagnostic::Kind::Warning, &message, span)
+            .with_help("remove it, or refer to it with `{name}` in a literal");
+
+        Some(diagnostic)
+    }
+}
+
+/// The variables the nodes of the document or of a body can refer to: the
+/// arguments of its component and the bindings around the node being read.
+#[derive(Default)]
+struct Scope {
+    arguments: HashMap<String, Value>,
+    /// The depth of the `let` whose attributes are being read.
+    binding: Option<u16>,
+    bindings: Vec<Binding>,
+    /// The length in bytes a literal may grow to once interpolated.
+    limit: usize,
+}
+
+impl Scope {
+    /// Drops the bindings that a node at `depth` is outside of.
+    fn leave(&mut self, depth: u16, trace: &mut VecDeque<Diagnostic>) {
+        #[rustfmt::skip]
+        let start = self.bindings.iter().position(|binding| binding.depth > depth).unwrap_or(self.bindings.len());
+
+        #[rustfmt::skip]
+        trace.extend(self.bindings.drain(start..).filter_map(|binding| binding.unused()));
+    }
+
+    fn close(self, trace: &mut VecDeque<Diagnostic>) {
+        trace.extend(self.bindings.iter().filter_map(Binding::unused));
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<Value> {
+        #[rustfmt::skip]
+        let binding = self.bindings.iter_mut().rev().find(|binding| binding.name == name);
+
+        match binding {
+            Some(binding) => {
+                binding.used = true;
+
+                Some(binding.value.clone())
+            }
+            None => self.arguments.get(name).cloned(),
+        }
+    }
+
+    /// Replaces every `{name}` in `text`, the value of a literal written at
+    /// `span`, with its variable, and `{{` and `}}` with a single brace.
+    ///
+    /// A brace that does not enclose a name is kept as it is, and a value is
+    /// inserted without being searched for variables again. A literal that
+    /// grows longer than the limit is reported as critical. Raw literals keep
+    /// their braces as they are.
+    fn interpolate(&mut self, text: &str, span: &Span, trace: &mut VecDeque<Diagnostic>) -> String {
+        let mut output = String::new();
+        let mut occurrences: HashMap<&str, usize> = HashMap::new();
+        let mut index = 0;
+
+        while let Some(char) = text[index..].chars().next() {
+            let rest = &text[index..];
+
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                output.push(char);
+                index += 2;
+
+                continue;
+            }
+
+            #[rustfmt::skip]
+            let reference = rest.strip_prefix('{').and_then(|rest| rest.find('}')).map(|end| &rest[..end + 2]).filter(|reference| is_variable(&reference[1..reference.len() - 1]));
+
+            let Some(reference) = reference else {
+                output.push(char);
+                index += char.len_utf8();
+
+                continue;
+            };
+
+            let occurrence = occurrences.entry(reference).or_default();
+            let name = &reference[1..reference.len() - 1];
+
+            index += reference.len();
+            *occurrence += 1;
+
+            if let Some(value) = self.lookup(name) {
+                output.push_str(&value.to_string());
+
+                if output.len() > self.limit {
+                    let message = format!(
+                        "this literal grows longer than the limit of {} bytes",
+                        self.limit
+                    );
+
+                    let diagnostic =
+                        Diagnostic::new(diagnostic::Kind::Critical, &message, span.clone())
+                            .with_note("the analysis stopped here");
+
+                    trace.push_back(diagnostic);
+
+                    break;
+                }
+
+                continue;
+            }
+
+            output.push_str(reference);
+
+            #[rustfmt::skip]
+            let offset = span.text().match_indices(reference).filter(|(offset, _)| !span.text()[..*offset].ends_with('{')).nth(*occurrence - 1).map(|(offset, _)| offset);
+
+            #[rustfmt::skip]
+            let location = offset.map_or(span.clone(), |offset| locate(span, offset, reference.len()));
+
+            let message = format!("the variable '{}' is not defined", name);
+
+            let diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, location)
+                .with_help("bind it before this node with `let | name \"value\"`");
+
+            trace.push_back(diagnostic);
+        }
+
+        output
+    }
+
+    /// The value of an attribute, which keeps the type of its variable when
+    /// it is written as a lone `{name}`.
+    fn value(&mut self, value: &Value, span: &Span, trace: &mut VecDeque<Diagnostic>) -> Value {
+        let Value::String(text) = value else {
+            return value.clone();
+        };
+
+        #[rustfmt::skip]
+        let name = text.strip_prefix('{').and_then(|text| text.strip_suffix('}')).filter(|name| is_variable(name));
+
+        match name.and_then(|name| self.lookup(name)) {
+            Some(value) => value,
+            None => Value::String(self.interpolate(text, span, trace)),
+        }
+    }
+
+    /// The node with its variables replaced, or nothing for the `let`
+    /// elements and the attributes they bind.
+    fn resolve(&mut self, node: Node, trace: &mut VecDeque<Diagnostic>) -> Option<Node> {
+        self.leave(node.depth, trace);
+
+        if let Some(depth) = self.binding {
+            if node.depth > depth {
+                if node.depth == depth + 1 {
+                    #[rustfmt::skip]
+                    let diagnostic = Diagnostic::new(diagnostic::Kind::Error, "a `let` cannot have children", node.span.clone())
+                        .with_help("write the variables as its attributes, as in `let | title \"Home\"`");
+
+                    trace.push_back(diagnostic);
+                }
+
+                return None;
+            }
+
+            if let Kind::Attribute(attribute) = &node.kind {
+                #[rustfmt::skip]
+                let value = attribute.value.as_ref().map_or(Value::Boolean(true), |value| match attribute.verbatim {
+                    true => value.clone(),
+                    false => self.value(value, &node.span, trace),
+                });
+
+                self.bindings.push(Binding {
+                    depth,
+                    name: attribute.name.clone(),
+                    span: node.span.clone(),
+                    used: false,
+                    value,
+                });
+
+                return None;
+            }
+
+            self.binding = None;
+        }
+
+        #[rustfmt::skip]
+        let kind = match &node.kind {
+            Kind::Element(element) if element.name == BINDING => {
+                self.binding = Some(node.depth);
+
+                return None;
+            },
+            Kind::Attribute(Attribute { name, value: Some(value), verbatim: false }) => {
+                Kind::from(Attribute::new(name.clone(), Some(self.value(value, &node.span, trace))))
+            },
+            Kind::Text(Text { value, verbatim: false }) => Kind::from(Text::new(self.interpolate(value, &node.span, trace))),
+            _ => return Some(node),
+        };
+
+        Some(Node { kind, ..node })
+    }
+}
+
+/// Whether a node written after an element at `depth` is one of its
+/// attributes or children.
+fn belongs(node: &Node, depth: u16) -> bool {
+    node.depth > depth || node.depth == depth && matches!(node.kind, Kind::Attribute(_))
+}
+
+/// How many of `nodes` belong to an element at `depth` written right before
+/// them.
+fn extent(nodes: &[Rc<Node>], depth: u16) -> usize {
+    nodes
+        .iter()
+        .position(|node| !belongs(node, depth))
+        .unwrap_or(nodes.len())
+}
+
+/// The slots of a body: its borrows, but for those that borrow from the
+/// borrowables of the body itself.
+fn slots(body: &[Rc<Node>]) -> Vec<Slot> {
+    let mut borrowables = HashSet::new();
+
+    for (index, node) in body.iter().enumerate() {
+        if !matches!(
+            node.kind,
+            Kind::ProcessingInstruction(ProcessingInstruction::Borrowable(_))
+        ) {
+            continue;
+        }
+
+        let length = extent(&body[index + 1..], node.depth);
+
+        for child in &body[index + 1..index + 1 + length] {
+            if let Kind::Element(element) = &child.kind {
+                if child.depth == node.depth + 1 {
+                    borrowables.insert(element.name.as_str());
+                }
+            }
+        }
+    }
+
+    #[rustfmt::skip]
+    let slots = body.iter().enumerate().filter_map(|(index, node)| {
+        let Kind::ProcessingInstruction(ProcessingInstruction::Borrow(borrow)) = &node.kind else {
+            return None;
+        };
+
+        if borrowables.contains(borrow.name.as_str()) {
+            return None;
+        }
+
+        Some(Slot {
+            name: borrow.name.clone(),
+            required: body.get(index + 1).is_none_or(|next| next.depth <= node.depth),
+            span: node.span.clone(),
+        })
+    }).collect();
+
+    slots
+}
+
+/// Splits the nodes that belong to an element at `depth` into its attributes
+/// and its children, the children moved to depths relative to the element.
+fn split(nodes: Vec<Rc<Node>>, depth: u16) -> (Vec<Rc<Node>>, Vec<Rc<Node>>) {
+    let (attributes, children): (Vec<_>, Vec<_>) =
+        nodes.into_iter().partition(|node| node.depth == depth);
+
+    #[rustfmt::skip]
+    let children = children.into_iter().map(|node| {
+        Rc::new(Node {
+            depth: node.depth - depth - 1,
+            ..Node::clone(&node)
+        })
+    }).collect();
+
+    (attributes, children)
+}
+
+/// Inlines the components of the document where they are used.
+///
+/// Components are declared before they are used, and expanded with the
+/// components declared up to that use; a name that starts with an uppercase
+/// letter and names no declared component is reported, but for one written in
+/// capitals only, such as `HTML`, which is left as an element. Their bodies are
+/// hygienic: a body only sees the parameters of its own component, and an argument is inserted
+/// without being searched for parameters again. The children of a call site
+/// fill the slots of the body, and keep seeing the slots of the body they were
+/// written in.
+///
+/// Variables are bound with `let | title "Home"` and referred to as `{title}`
+/// in text and attribute values. A binding is seen by the nodes written after
+/// it at its depth or deeper, and a body sees its parameters as variables,
+/// but not the variables of its call site.
 pub struct Analyzer<I>
 where
     I: Iterator<Item = Result>,
 {
+    /// The borrowables of the document around the node being read, with their
+    /// depths.
+    borrowables: Vec<(u16, String)>,
+    components: HashMap<String, Rc<Component>>,
+    finished: bool,
+    /// How many nodes of bodies and slot contents were read so far.
+    inlined: usize,
+    limits: Limits,
+    /// The scopes of the document and of the bodies being inlined.
+    scopes: Vec<Scope>,
+    stack: Vec<Frame>,
     trace: VecDeque<Diagnostic>,
     upstream: Peekable<I>,
 }
 
+impl<I> Analyzer<I>
+where
+    I: Iterator<Item = Result>,
+{
+    fn error(&mut self, message: &str, span: Span) {
+        let diagnostic = Diagnostic::new(diagnostic::Kind::Error, message, span);
+
+        self.trace.push_back(diagnostic);
+    }
+
+    fn resolve(&mut self, scope: Option<usize>, node: Node) -> Option<Node> {
+        let node = match scope {
+            Some(scope) => self.scopes[scope].resolve(node, &mut self.trace),
+            None => Some(node),
+        };
+
+        // A literal that grew over the limit stops the expansion.
+        #[rustfmt::skip]
+        let critical = self.trace.iter().any(|diagnostic| matches!(diagnostic.kind, diagnostic::Kind::Critical));
+
+        if critical {
+            self.stack.clear();
+            self.finished = true;
+
+            return None;
+        }
+
+        node
+    }
+
+    /// Takes the nodes that belong to the element at `depth` just read,
+    /// resolved in the document scope if `resolve` is set.
+    fn take(&mut self, depth: u16, resolve: bool) -> Vec<Rc<Node>> {
+        let mut nodes = Vec::new();
+
+        while let Some(result) = self.upstream.peek() {
+            if matches!(result, Result::Value(node) if !belongs(node, depth)) {
+                break;
+            }
+
+            match self.upstream.next() {
+                Some(Result::Diagnostic(diagnostic)) => self.trace.push_back(diagnostic),
+                Some(Result::Value(node)) if !resolve => nodes.push(node),
+                Some(Result::Value(node)) => {
+                    if let Some(node) = self.resolve(Some(0), Node::clone(&node)) {
+                        nodes.push(Rc::new(node));
+                    }
+                }
+                None => break,
+            }
+        }
+
+        nodes
+    }
+
+    fn declare(&mut self, node: &Node, nodes: Vec<Rc<Node>>) {
+        let (attributes, body) = split(nodes, node.depth);
+
+        let mut name = None;
+        let mut parameters = Vec::new();
+
+        for attribute in attributes {
+            let Kind::Attribute(data) = &attribute.kind else {
+                continue;
+            };
+
+            if data.name == "name" {
+                name = data
+                    .value
+                    .as_ref()
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+
+                continue;
+            }
+
+            parameters.push(Parameter {
+                default: data.value.clone(),
+                name: data.name.clone(),
+                span: attribute.span.clone(),
+            });
+        }
+
+        let Some(name) = name else {
+            let diagnostic = Diagnostic::new(
+                diagnostic::Kind::Error,
+                "a component needs a name",
+                node.span.clone(),
+            )
+            .with_help("name it with an attribute, as in `component | name \"Card\"`");
+
+            return self.trace.push_back(diagnostic);
+        };
+
+        if !is_component(&name) {
+            let message = format!(
+                "the component name '{}' must start with an uppercase letter",
+                name
+            );
+
+            return self.error(&message, node.span.clone());
+        }
+
+        if let Some(component) = self.components.get(&name) {
+            let message = format!("the component '{}' is declared more than once", name);
+
+            let diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, node.span.clone())
+                .with_label(component.span.clone(), "first declared here");
+
+            return self.trace.push_back(diagnostic);
+        }
+
+        let component = Component {
+            slots: slots(&body),
+            body: body.into(),
+            name: name.clone(),
+            parameters,
+            span: node.span.clone(),
+        };
+
+        self.components.insert(name, Rc::new(component));
+    }
+
+    fn arguments(&mut self, component: &Component, call: &Use) -> HashMap<String, Value> {
+        let mut arguments = HashMap::new();
+
+        for node in &call.arguments {
+            let Kind::Attribute(attribute) = &node.kind else {
+                continue;
+            };
+
+            #[rustfmt::skip]
+            let declared = component.parameters.iter().any(|parameter| parameter.name == attribute.name);
+
+            if !declared {
+                let message = format!(
+                    "the component '{}' has no parameter '{}'",
+                    component.name, attribute.name
+                );
+
+                let diagnostic =
+                    Diagnostic::new(diagnostic::Kind::Error, &message, node.span.clone())
+                        .with_label(component.span.clone(), "the component is declared here");
+
+                self.trace.push_back(diagnostic);
+
+                continue;
+            }
+
+            #[rustfmt::skip]
+            let value = attribute.value.clone().unwrap_or(Value::Boolean(true));
+
+            arguments.insert(attribute.name.clone(), value);
+        }
+
+        for parameter in &component.parameters {
+            if arguments.contains_key(&parameter.name) {
+                continue;
+            }
+
+            let Some(default) = &parameter.default else {
+                let message = format!(
+                    "the component '{}' is missing the parameter '{}'",
+                    component.name, parameter.name
+                );
+
+                let diagnostic =
+                    Diagnostic::new(diagnostic::Kind::Error, &message, call.span.clone())
+                        .with_label(parameter.span.clone(), "the parameter is declared here");
+
+                self.trace.push_back(diagnostic);
+
+                continue;
+            };
+
+            arguments.insert(parameter.name.clone(), default.clone());
+        }
+
+        arguments
+    }
+
+    /// Whether a borrowable of the document named `name` is in scope.
+    fn is_borrowable(&self, name: &str) -> bool {
+        self.borrowables
+            .iter()
+            .any(|(_, borrowable)| borrowable == name)
+    }
+
+    /// Tracks the borrowables of the document that `node`, read from it, is
+    /// in the scope of.
+    fn enter(&mut self, node: &Node) {
+        self.borrowables.retain(|(depth, _)| *depth <= node.depth);
+
+        if let Kind::ProcessingInstruction(ProcessingInstruction::Borrowable(borrowable)) =
+            &node.kind
+        {
+            self.borrowables.push((node.depth, borrowable.name.clone()));
+        }
+    }
+
+    /// Groups the children of a call site by the slot they fill, reporting
+    /// the slots that do not exist, are filled twice or are left empty.
+    fn fills(&mut self, component: &Component, call: &Use) -> HashMap<String, Rc<[Rc<Node>]>> {
+        let mut fills: HashMap<String, Fill> = HashMap::new();
+        let mut index = 0;
+
+        while let Some(node) = call.children.get(index) {
+            let length = 1 + extent(&call.children[index + 1..], node.depth);
+            let nodes = &call.children[index..index + length];
+
+            index += length;
+
+            #[rustfmt::skip]
+            let (name, nodes) = match &node.kind {
+                Kind::ProcessingInstruction(ProcessingInstruction::Borrowable(borrowable)) => {
+                    (borrowable.name.as_str(), split(nodes[1..].to_vec(), node.depth).1)
+                },
+                _ => (CHILDREN, nodes.to_vec()),
+            };
+
+            #[rustfmt::skip]
+            let loose = !matches!(node.kind, Kind::ProcessingInstruction(ProcessingInstruction::Borrowable(_)));
+
+            if !component.slots.iter().any(|slot| slot.name == name) {
+                #[rustfmt::skip]
+                let diagnostic = match loose {
+                    true => Diagnostic::new(diagnostic::Kind::Error, &format!("the component '{}' does not take children", component.name), node.span.clone())
+                        .with_help("declare where they go with a `children <-` slot in its body"),
+                    false => Diagnostic::new(diagnostic::Kind::Error, &format!("the component '{}' has no slot '{}'", component.name, name), node.span.clone())
+                        .with_label(component.span.clone(), "the component is declared here"),
+                };
+
+                self.trace.push_back(diagnostic);
+
+                continue;
+            }
+
+            match fills.get_mut(name) {
+                Some(fill) if loose && fill.loose => {
+                    fill.nodes.extend(nodes);
+                }
+                Some(fill) => {
+                    let message = format!("the slot '{}' is filled more than once", name);
+
+                    let diagnostic =
+                        Diagnostic::new(diagnostic::Kind::Error, &message, node.span.clone())
+                            .with_label(fill.span.clone(), "first filled here");
+
+                    self.trace.push_back(diagnostic);
+                }
+                None => {
+                    let span = node.span.clone();
+
+                    fills.insert(name.to_string(), Fill { loose, nodes, span });
+                }
+            }
+        }
+
+        for slot in &component.slots {
+            if !slot.required || fills.contains_key(&slot.name) || self.is_borrowable(&slot.name) {
+                continue;
+            }
+
+            let message = format!(
+                "the component '{}' is missing the slot '{}'",
+                component.name, slot.name
+            );
+
+            let diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, call.span.clone())
+                .with_label(slot.span.clone(), "the slot is declared here");
+
+            self.trace.push_back(diagnostic);
+        }
+
+        #[rustfmt::skip]
+        let fills = fills.into_iter().map(|(name, fill)| (name, fill.nodes.into())).collect();
+
+        fills
+    }
+
+    /// The frames of the components being expanded, innermost first,
+    /// following the content of a slot back to the body it was written in.
+    fn enclosing(&self) -> Vec<usize> {
+        let mut enclosing = Vec::new();
+        let mut end = self.stack.len();
+
+        while let Some(index) = end.checked_sub(1) {
+            match &self.stack[index].origin {
+                Origin::Component { .. } => {
+                    enclosing.push(index);
+                    end = index;
+                }
+                Origin::Slot(caller) => {
+                    end = *caller;
+                }
+            }
+        }
+
+        enclosing
+    }
+
+    fn expand(&mut self, call: Use) {
+        let Some(component) = self.components.get(&call.name).cloned() else {
+            let message = format!("the component '{}' is not declared", call.name);
+
+            let diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, call.span)
+                .with_help("declare it with `component | name` before using it");
+
+            return self.trace.push_back(diagnostic);
+        };
+
+        #[rustfmt::skip]
+        let expanding: Vec<(&str, &Span)> = self.enclosing().into_iter().filter_map(|index| match &self.stack[index].origin {
+            Origin::Component { component, span, .. } => Some((component.name.as_str(), span)),
+            Origin::Slot(_) => None,
+        }).collect();
+
+        if expanding.iter().any(|(name, _)| *name == call.name) {
+            let message = format!("the component '{}' expands into itself", call.name);
+
+            let mut diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, call.span);
+
+            for (name, span) in expanding {
+                let label = format!("'{}' is expanded here", name);
+
+                diagnostic = diagnostic.with_label(span.clone(), &label);
+            }
+
+            return self.trace.push_back(diagnostic);
+        }
+
+        let arguments = self.arguments(&component, &call);
+        let fills = self.fills(&component, &call);
+
+        self.scopes.push(Scope {
+            arguments,
+            limit: self.limits.length,
+            ..Scope::default()
+        });
+
+        self.stack.push(Frame {
+            depth: call.depth,
+            index: 0,
+            nodes: component.body.clone(),
+            origin: Origin::Component {
+                component,
+                fills,
+                span: call.span,
+            },
+            scope: Some(self.scopes.len() - 1),
+        });
+    }
+
+    /// Inlines what fills the slot `name` borrowed at `depth` by the frame
+    /// `top`, or its fallback content, if the borrow is a slot.
+    ///
+    /// A required slot that nothing fills is left as a borrow of the
+    /// borrowable of the document named after it, if there is one.
+    fn project(&mut self, top: usize, name: &str, depth: u16) -> bool {
+        #[rustfmt::skip]
+        let fill = self.enclosing().first().and_then(|&owner| match &self.stack[owner].origin {
+            Origin::Component { component, fills, .. } => {
+                let slot = component.slots.iter().find(|slot| slot.name == name)?;
+                let fill = fills.get(name).cloned();
+
+                match fill.is_none() && slot.required && self.is_borrowable(name) {
+                    true => None,
+                    false => Some((owner, fill)),
+                }
+            },
+            _ => None,
+        });
+
+        let Some((owner, fill)) = fill else {
+            return false;
+        };
+
+        let frame = &mut self.stack[top];
+        let rest = &frame.nodes[frame.index..];
+        let relative = depth - frame.depth;
+
+        #[rustfmt::skip]
+        let length = rest.iter().position(|node| node.depth <= relative).unwrap_or(rest.len());
+
+        let fallback = split(rest[..length].to_vec(), relative).1;
+
+        frame.index += length;
+
+        #[rustfmt::skip]
+        let (nodes, caller, scope) = match fill {
+            Some(nodes) => (nodes, owner, None),
+            None => (fallback.into(), top + 1, frame.scope),
+        };
+
+        self.stack.push(Frame {
+            depth,
+            index: 0,
+            nodes,
+            origin: Origin::Slot(caller),
+            scope,
+        });
+
+        true
+    }
+
+    fn stop(&mut self) {
+        let Some(Origin::Component { span, .. }) = self.stack.first().map(|frame| &frame.origin)
+        else {
+            return;
+        };
+
+        let message = format!(
+            "the components expand into more than {} nodes",
+            self.limits.nodes
+        );
+
+        let diagnostic = Diagnostic::new(diagnostic::Kind::Critical, &message, span.clone())
+            .with_note("the analysis stopped here");
+
+        self.trace.push_back(diagnostic);
+        self.stack.clear();
+        self.finished = true;
+    }
+
+    /// The next node of the innermost body or slot content being inlined.
+    fn inline(&mut self) -> Option<Result> {
+        let top = self.stack.len().checked_sub(1)?;
+        let frame = &mut self.stack[top];
+
+        let Some(node) = frame.nodes.get(frame.index).cloned() else {
+            let frame = self.stack.pop();
+
+            if matches!(
+                frame.map(|frame| frame.origin),
+                Some(Origin::Component { .. })
+            ) {
+                if let Some(scope) = self.scopes.pop() {
+                    scope.close(&mut self.trace);
+                }
+            }
+
+            return None;
+        };
+
+        frame.index += 1;
+        self.inlined += 1;
+
+        // A body can use other components many times over without writing
+        // out a node, so the work is bounded here rather than by the output.
+        if self.inlined > self.limits.nodes {
+            self.stop();
+
+            return None;
+        }
+
+        let frame = &mut self.stack[top];
+        let (depth, scope) = (frame.depth, frame.scope);
+        let node = self.resolve(scope, rebase(&node, depth))?;
+
+        let frame = &mut self.stack[top];
+        let rest = &frame.nodes[frame.index..];
+        let length = extent(rest, node.depth - frame.depth);
+
+        match &node.kind {
+            Kind::Element(element) if element.name == DECLARATION => {
+                frame.index += length;
+
+                let message = "a component cannot be declared inside another component";
+
+                self.error(message, node.span);
+
+                None
+            }
+            Kind::Element(element) if is_use(&self.components, &element.name) => {
+                let written = rest[..length].to_vec();
+
+                frame.index += length;
+
+                #[rustfmt::skip]
+                let nodes = written.iter().filter_map(|child| self.resolve(scope, rebase(child, depth))).map(Rc::new).collect();
+
+                let (arguments, children) = split(nodes, node.depth);
+
+                self.expand(Use {
+                    arguments,
+                    children,
+                    depth: node.depth,
+                    name: element.name.clone(),
+                    span: node.span.clone(),
+                });
+
+                None
+            }
+            Kind::ProcessingInstruction(ProcessingInstruction::Borrow(borrow)) => {
+                match self.project(top, &borrow.name, node.depth) {
+                    true => None,
+                    false => Some(Result::from(node)),
+                }
+            }
+            _ => Some(Result::from(node)),
+        }
+    }
+}
+
 impl<I> Iterator for Analyzer<I>
 where
     I: Iterator<Item = Result>,
@@ -21,11 +997,65 @@ where
     type Item = Result;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(diagnostic) = self.trace.pop_front() {
-            return Some(Result::from(diagnostic));
-        }
+        loop {
+            if let Some(diagnostic) = self.trace.pop_front() {
+                return Some(Result::from(diagnostic));
+            }
+
+            if self.finished {
+                return None;
+            }
+
+            if !self.stack.is_empty() {
+                if let Some(result) = self.inline() {
+                    return Some(result);
+                }
 
-        self.upstream.next()
+                continue;
+            }
+
+            let Some(result) = self.upstream.next() else {
+                std::mem::take(&mut self.scopes[0]).close(&mut self.trace);
+
+                match self.trace.is_empty() {
+                    true => return None,
+                    false => continue,
+                }
+            };
+
+            let Result::Value(node) = result else {
+                return Some(result);
+            };
+
+            let Some(node) = self.resolve(Some(0), Node::clone(&node)) else {
+                continue;
+            };
+
+            self.enter(&node);
+
+            let Kind::Element(element) = &node.kind else {
+                return Some(Result::from(node));
+            };
+
+            if element.name == DECLARATION {
+                let nodes = self.take(node.depth, false);
+
+                self.declare(&node, nodes);
+            } else if is_use(&self.components, &element.name) {
+                let nodes = self.take(node.depth, true);
+                let (arguments, children) = split(nodes, node.depth);
+
+                self.expand(Use {
+                    arguments,
+                    children,
+                    depth: node.depth,
+                    name: element.name.clone(),
+                    span: node.span.clone(),
+                });
+            } else {
+                return Some(Result::from(node));
+            }
+        }
     }
 }
 
@@ -34,10 +1064,27 @@ where
     I: Iterator<Item = Result>,
 {
     pub fn new(upstream: I) -> Self {
+        Self::with_limits(upstream, Limits::default())
+    }
+
+    pub fn with_limits(upstream: I, limits: Limits) -> Self {
         let upstream = upstream.peekable();
         let trace = VecDeque::new();
 
-        Self { trace, upstream }
+        Self {
+            borrowables: Vec::new(),
+            components: HashMap::new(),
+            finished: false,
+            inlined: 0,
+            limits,
+            scopes: vec![Scope {
+                limit: limits.length,
+                ..Scope::default()
+            }],
+            stack: Vec::new(),
+            trace,
+            upstream,
+        }
     }
 }
 
@@ -49,3 +1096,262 @@ where
         Self::new(upstream)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::analyzer::{
+        lexical::tokenizer,
+        source::Source,
+        syntactic::{converter, limits::Limits, Kind, Result},
+    };
+
+    use super::Analyzer;
+
+    fn run(text: &str) -> Vec<Result> {
+        let source = Source::string("test.mt", text);
+        let tokens = tokenizer::Analyzer::new(source.clone(), source.chars());
+
+        Analyzer::new(converter::Analyzer::new(tokens)).collect()
+    }
+
+    #[test]
+    fn test() {
+        let text = "
+            component | name \"Badge\" | text { span { \"{text}\" } }
+            component | name \"Card\" | title | level 2 {
+                h2 | data-level \"{level}\" { \"{title}\" Badge | text \"{title}!\" }
+            }
+            main { Card | title \"Hi {{level}}\" }
+        ";
+
+        #[rustfmt::skip]
+        let nodes: Vec<String> = run(text).iter().map(|result| match result {
+            Result::Diagnostic(diagnostic) => format!("error {}", diagnostic.message),
+            Result::Value(node) => match &node.kind {
+                Kind::Attribute(attribute) => format!("{} {} {:?}", node.depth, attribute.name, attribute.value),
+                Kind::Element(element) => format!("{} {}", node.depth, element.name),
+                Kind::Text(text) => format!("{} {:?}", node.depth, text.value),
+                kind => format!("{} {}", node.depth, kind),
+            },
+        }).collect();
+
+        assert_eq!(
+            nodes,
+            [
+                "0 main",
+                "1 h2",
+                "1 data-level Some(Integer(2))",
+                "2 \"Hi {level}\"",
+                "2 span",
+                "3 \"Hi {level}!\"",
+            ]
+        );
+
+        let text = "
+            component | name \"A\" | x { B }
+            component | name \"B\" { A | x 1 }
+            A | y { p } Cardd { p } HTML { BODY }
+        ";
+
+        let results = run(text);
+
+        #[rustfmt::skip]
+        let messages: Vec<(&str, &str)> = results.iter().filter_map(|result| match result {
+            Result::Diagnostic(diagnostic) => Some((diagnostic.message.as_str(), diagnostic.span.text())),
+            Result::Value(_) => None,
+        }).collect();
+
+        assert_eq!(
+            messages,
+            [
+                ("the component 'A' has no parameter 'y'", "| y"),
+                ("the component 'A' is missing the parameter 'x'", "A"),
+                ("the component 'A' does not take children", "p"),
+                ("the component 'A' expands into itself", "A"),
+                ("the component 'Cardd' is not declared", "Cardd"),
+            ]
+        );
+
+        #[rustfmt::skip]
+        let elements: Vec<(u16, &str)> = results.iter().filter_map(|result| match result {
+            Result::Value(node) => match &node.kind {
+                Kind::Element(element) => Some((node.depth, element.name.as_str())),
+                _ => None,
+            },
+            Result::Diagnostic(_) => None,
+        }).collect();
+
+        assert_eq!(elements, [(0, "HTML"), (1, "BODY")]);
+
+        let text = "
+            component | name \"Card\" { h2 { header <- } div { children <- } p { footer <- { \"none\" } } }
+            Card { header -> { \"title\" } \"body\" }
+            Card { children -> { \"a\" } \"b\" }
+            nav -> { a } Card { header -> { \"c\" } nav <- \"d\" }
+            header -> { b } Card { \"e\" }
+        ";
+
+        let results = run(text);
+
+        #[rustfmt::skip]
+        let nodes: Vec<String> = results.iter().map(|result| match result {
+            Result::Diagnostic(diagnostic) => format!("{} at {:?}", diagnostic.message, diagnostic.span.text()),
+            Result::Value(node) => match &node.kind {
+                Kind::Text(text) => format!("{} {:?}", node.depth, text.value),
+                kind => format!("{} {}", node.depth, kind),
+            },
+        }).collect();
+
+        assert_eq!(
+            nodes,
+            [
+                "0 element",
+                "1 \"title\"",
+                "0 element",
+                "1 \"body\"",
+                "0 element",
+                "1 \"none\"",
+                "the slot 'children' is filled more than once at \"\\\"b\\\"\"",
+                "the component 'Card' is missing the slot 'header' at \"Card\"",
+                "0 element",
+                "0 element",
+                "1 \"a\"",
+                "0 element",
+                "1 \"none\"",
+                "0 borrowable",
+                "1 element",
+                "0 element",
+                "1 \"c\"",
+                "0 element",
+                "1 borrow",
+                "1 \"d\"",
+                "0 element",
+                "1 \"none\"",
+                "0 borrowable",
+                "1 element",
+                "0 element",
+                "1 borrow",
+                "0 element",
+                "1 \"e\"",
+                "0 element",
+                "1 \"none\"",
+            ]
+        );
+
+        let text = "
+            let | site \"Home\" | year 2024 | draft
+            p | data-year \"{year}\" { \"{site} {{site}} {missing}\" }
+            div { let | inner \"a\" } \"{inner}\"
+            p | title r\"{year}\" { r\"{site} {{x}}\" \"\"\"{site}\"\"\" }
+        ";
+
+        let results = run(text);
+
+        #[rustfmt::skip]
+        let nodes: Vec<String> = results.iter().map(|result| match result {
+            Result::Diagnostic(diagnostic) => format!("{} at {:?} {}:{}", diagnostic.message, diagnostic.span.text(), diagnostic.span.start.row, diagnostic.span.start.col),
+            Result::Value(node) => match &node.kind {
+                Kind::Attribute(attribute) => format!("{} {} {:?}", node.depth, attribute.name, attribute.value),
+                Kind::Text(text) => format!("{} {:?}", node.depth, text.value),
+                kind => format!("{} {}", node.depth, kind),
+            },
+        }).collect();
+
+        assert_eq!(
+            nodes,
+            [
+                "0 element",
+                "0 data-year Some(Integer(2024))",
+                "1 \"Home {site} {missing}\"",
+                "the variable 'missing' is not defined at \"{missing}\" 2:54",
+                "0 element",
+                "0 \"{inner}\"",
+                "the variable 'inner' is never used at \"inner\" 3:24",
+                "the variable 'inner' is not defined at \"{inner}\" 3:37",
+                "0 element",
+                "0 title Some(String(\"{year}\"))",
+                "1 \"{site} {{x}}\"",
+                "1 \"{site}\"",
+                "the variable 'draft' is never used at \"draft\" 1:44",
+            ]
+        );
+
+        let text = "script { \"function f() {x} {{y}}\" r\"function g() {x}\" }";
+
+        #[rustfmt::skip]
+        let texts: Vec<String> = run(text).iter().map(|result| match result {
+            Result::Diagnostic(diagnostic) => format!("error {}", diagnostic.message),
+            Result::Value(node) => match &node.kind {
+                Kind::Text(text) => text.value.clone(),
+                kind => kind.to_string(),
+            },
+        }).collect();
+
+        assert_eq!(
+            texts,
+            [
+                "element",
+                "function f() {x} {y}",
+                "error the variable 'x' is not defined",
+                "function g() {x}",
+            ]
+        );
+
+        let text = "
+            component | name \"C\" {}
+            component | name \"B\" { C C }
+            component | name \"A\" { B B }
+            A A A
+        ";
+
+        let source = Source::string("test.mt", text);
+        let tokens = tokenizer::Analyzer::new(source.clone(), source.chars());
+        let limits = Limits {
+            nodes: 10,
+            ..Limits::default()
+        };
+
+        let results: Vec<Result> =
+            Analyzer::with_limits(converter::Analyzer::new(tokens), limits).collect();
+
+        #[rustfmt::skip]
+        let messages: Vec<(String, &str)> = results.iter().filter_map(|result| match result {
+            Result::Diagnostic(diagnostic) => Some((diagnostic.message.clone(), diagnostic.span.text())),
+            Result::Value(_) => None,
+        }).collect();
+
+        assert_eq!(
+            messages,
+            [(
+                "the components expand into more than 10 nodes".to_string(),
+                "A"
+            )]
+        );
+
+        let text = "let | a \"abcd\" | b \"{a}{a}\" | c \"{b}{b}\" | d \"{c}{c}\" p { \"{d}\" }";
+        let source = Source::string("test.mt", text);
+        let tokens = tokenizer::Analyzer::new(source.clone(), source.chars());
+
+        let limits = Limits {
+            length: 16,
+            ..Limits::default()
+        };
+
+        let results: Vec<Result> =
+            Analyzer::with_limits(converter::Analyzer::new(tokens), limits).collect();
+
+        #[rustfmt::skip]
+        let messages: Vec<(String, &str)> = results.iter().filter_map(|result| match result {
+            Result::Diagnostic(diagnostic) => Some((diagnostic.message.clone(), diagnostic.span.text())),
+            Result::Value(_) => None,
+        }).collect();
+
+        assert_eq!(
+            messages,
+            [(
+                "this literal grows longer than the limit of 16 bytes".to_string(),
+                "| d \"{c}{c}\""
+            )]
+        );
+    }
+}
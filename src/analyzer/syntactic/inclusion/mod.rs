@@ -0,0 +1,405 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+
+use crate::analyzer::{
+    diagnostic::{self, Diagnostic, Span},
+    lexical::tokenizer,
+    source::{self, Chars, Source},
+};
+
+use super::{converter, limits::Limits, Kind, Node, ProcessingInstruction, Result};
+
+/// A file being spliced into the document.
+struct Frame {
+    depth: u16,
+    /// The path as the include writes it.
+    name: String,
+    path: PathBuf,
+    span: Span,
+    upstream: Box<dyn Iterator<Item = Result>>,
+}
+
+/// The path a source is known by, to tell when a file includes itself.
+fn identity(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Undoes the `%20` escapes of the path of a URI.
+fn decode(path: &str) -> PathBuf {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while let Some(&byte) = bytes.get(index) {
+        #[rustfmt::skip]
+        let escape = bytes.get(index + 1..index + 3).filter(|hex| hex.iter().all(u8::is_ascii_hexdigit));
+
+        match (byte, escape) {
+            (b'%', Some(hex)) => {
+                let hex = String::from_utf8_lossy(hex);
+
+                decoded.extend(u8::from_str_radix(&hex, 16).ok());
+                index += 3;
+            }
+            _ => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+
+    PathBuf::from(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+/// The file a source was read from, if any; editors name their documents
+/// with `file://` URIs.
+fn location(source: &Source) -> Option<PathBuf> {
+    match source.kind {
+        source::Kind::File => Some(PathBuf::from(&source.name)),
+        _ => source.name.strip_prefix("file://").map(decode),
+    }
+}
+
+/// The directory the includes of a source are resolved against: the one
+/// holding its file, or the working directory for any other input.
+fn directory(source: &Source) -> PathBuf {
+    location(source)
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
+}
+
+/// Splices the files named by `"header.mt" <-` into the node stream, at the
+/// depth of the include.
+///
+/// Paths are resolved against the directory of the including file and have to
+/// stay inside the directory of the document, and a file that ends up
+/// including itself is reported with every include on the way. Going over the
+/// limit of includes, or of nested ones, stops the analysis.
+pub struct Analyzer<I>
+where
+    I: Iterator<Item = Result>,
+{
+    finished: bool,
+    /// How many includes were read so far.
+    included: usize,
+    limits: Limits,
+    path: Option<PathBuf>,
+    /// The directory included files have to be in, or `None` when the input
+    /// cannot include files at all.
+    root: Option<PathBuf>,
+    stack: Vec<Frame>,
+    trace: VecDeque<Diagnostic>,
+    upstream: I,
+}
+
+impl<I> Analyzer<I>
+where
+    I: Iterator<Item = Result>,
+{
+    /// Resolves the path of an include to the regular file it names inside
+    /// the root, so that a document cannot read files out of its directory or
+    /// block on a device; otherwise gives the reason it cannot be included.
+    fn resolve(&self, path: &str, span: &Span) -> std::result::Result<PathBuf, String> {
+        let Some(root) = &self.root else {
+            return Err("includes are turned off for this input".into());
+        };
+
+        #[rustfmt::skip]
+        let resolved = std::fs::canonicalize(directory(&span.source).join(path)).map_err(|error| error.to_string())?;
+
+        if !resolved.starts_with(root) {
+            return Err(format!("the file is outside of '{}'", root.display()));
+        }
+
+        match std::fs::metadata(&resolved) {
+            Ok(metadata) if metadata.is_file() => Ok(resolved),
+            Ok(_) => Err("it is not a regular file".into()),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    /// Stops the nodes at an include over the limits; the files a document
+    /// includes are read before any of their nodes reach the other limits.
+    fn check(&mut self, span: &Span) -> Option<Diagnostic> {
+        self.included += 1;
+
+        let message = if self.included > self.limits.includes {
+            format!(
+                "the document includes more than {} files",
+                self.limits.includes
+            )
+        } else if self.stack.len() >= usize::from(self.limits.depth) {
+            format!(
+                "this include is nested deeper than the limit of {} levels",
+                self.limits.depth
+            )
+        } else {
+            return None;
+        };
+
+        self.finished = true;
+
+        let diagnostic = Diagnostic::new(diagnostic::Kind::Critical, &message, span.clone())
+            .with_note("the analysis stopped here");
+
+        Some(diagnostic)
+    }
+
+    fn include(&mut self, path: &str, depth: u16, span: Span) {
+        if let Some(diagnostic) = self.check(&span) {
+            return self.trace.push_back(diagnostic);
+        }
+
+        let identity = match self.resolve(path, &span) {
+            Ok(identity) => identity,
+            Err(reason) => {
+                let message = format!("cannot include '{}': {}", path, reason);
+
+                let diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, span);
+
+                return self.trace.push_back(diagnostic);
+            }
+        };
+
+        #[rustfmt::skip]
+        let cycle = self.path.as_ref() == Some(&identity) || self.stack.iter().any(|frame| frame.path == identity);
+
+        if cycle {
+            let message = format!("including '{}' makes a cycle", path);
+
+            let mut diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, span)
+                .with_primary_label("the file is included again here");
+
+            for frame in self.stack.iter().rev() {
+                let label = format!("'{}' is included here", frame.name);
+
+                diagnostic = diagnostic.with_label(frame.span.clone(), &label);
+            }
+
+            return self.trace.push_back(diagnostic);
+        }
+
+        #[rustfmt::skip]
+        let (source, trace) = match Source::file(&identity.to_string_lossy(), Some(self.limits.bytes)) {
+            Ok(decoded) => decoded,
+            Err(error) => {
+                let message = format!("cannot include '{}': {}", path, error);
+
+                let diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, span);
+
+                return self.trace.push_back(diagnostic);
+            }
+        };
+
+        self.trace.extend(trace);
+
+        let upstream = tokenizer::Analyzer::<Chars>::new(source.clone(), source.chars())
+            .with_limit(self.limits.length);
+
+        let upstream =
+            converter::Analyzer::new(upstream).with_limit(self.limits.depth.saturating_sub(depth));
+
+        self.stack.push(Frame {
+            depth,
+            name: path.to_string(),
+            path: identity,
+            span,
+            upstream: Box::new(upstream),
+        });
+    }
+
+    fn read(&mut self) -> Option<Result> {
+        loop {
+            let Some(frame) = self.stack.last_mut() else {
+                return self.upstream.next();
+            };
+
+            let Some(result) = frame.upstream.next() else {
+                self.stack.pop();
+
+                continue;
+            };
+
+            let Result::Value(node) = result else {
+                return Some(result);
+            };
+
+            let node = Node {
+                depth: node.depth.saturating_add(frame.depth),
+                ..Node::clone(&node)
+            };
+
+            return Some(Result::from(node));
+        }
+    }
+}
+
+impl<I> Iterator for Analyzer<I>
+where
+    I: Iterator<Item = Result>,
+{
+    type Item = Result;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(diagnostic) = self.trace.pop_front() {
+                return Some(Result::from(diagnostic));
+            }
+
+            if self.finished {
+                return None;
+            }
+
+            let result = self.read()?;
+
+            let Result::Value(node) = &result else {
+                return Some(result);
+            };
+
+            let Kind::ProcessingInstruction(ProcessingInstruction::Include(include)) = &node.kind
+            else {
+                return Some(result);
+            };
+
+            self.include(&include.path, node.depth, node.span.clone());
+        }
+    }
+}
+
+impl<I> Analyzer<I>
+where
+    I: Iterator<Item = Result>,
+{
+    pub fn new(upstream: I, source: &Source, limits: Limits) -> Self {
+        let root = match directory(source) {
+            root if root.as_os_str().is_empty() => identity(Path::new(".")),
+            root => identity(&root),
+        };
+
+        Self {
+            finished: false,
+            included: 0,
+            limits,
+            path: location(source).as_deref().map(identity),
+            root: Some(root),
+            stack: Vec::new(),
+            trace: VecDeque::new(),
+            upstream,
+        }
+    }
+
+    /// Reports every include instead of reading the file it names, for input
+    /// that is not trusted to read files.
+    pub fn without_includes(mut self) -> Self {
+        self.root = None;
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::analyzer::{
+        lexical::tokenizer,
+        source::Source,
+        syntactic::{converter, Kind, Result},
+    };
+
+    use super::{Analyzer, Limits};
+
+    fn run(path: &Path, limits: Limits, disabled: bool) -> std::io::Result<Vec<String>> {
+        let (source, _) = Source::file(&path.to_string_lossy(), None)?;
+
+        let tokens = tokenizer::Analyzer::new(source.clone(), source.chars());
+        let analyzer = Analyzer::new(converter::Analyzer::new(tokens), &source, limits);
+
+        let analyzer = match disabled {
+            true => analyzer.without_includes(),
+            false => analyzer,
+        };
+
+        #[rustfmt::skip]
+        let nodes = analyzer.map(|result| match result {
+            Result::Diagnostic(diagnostic) => {
+                let labels: Vec<&str> = diagnostic.labels.iter().map(|label| label.span.text()).collect();
+
+                format!("{} at {} from {:?}", diagnostic.message, diagnostic.span.text(), labels)
+            },
+            Result::Value(node) => match &node.kind {
+                Kind::Element(element) => format!("{} {}", node.depth, element.name),
+                kind => format!("{} {}", node.depth, kind),
+            },
+        }).collect();
+
+        Ok(nodes)
+    }
+
+    #[test]
+    fn test() -> std::io::Result<()> {
+        let directory = std::env::temp_dir().join(format!("markup-type-{}", std::process::id()));
+        let site = directory.join("site");
+
+        std::fs::create_dir_all(site.join("parts"))?;
+        std::fs::write(directory.join("secret.mt"), "secret")?;
+        std::fs::write(site.join("index.mt"), "body { \"parts/nav.mt\" <- }")?;
+        std::fs::write(
+            site.join("parts/nav.mt"),
+            "nav { \"../index.mt\" <- a \"../../secret.mt\" <- }",
+        )?;
+
+        let root = std::fs::canonicalize(&site)?;
+
+        for index in 0..8 {
+            let text = format!("\"{0}.mt\" <- \"{0}.mt\" <-", index + 1);
+
+            std::fs::write(site.join(format!("{}.mt", index)), text)?;
+        }
+
+        let limits = Limits {
+            depth: 8,
+            includes: 6,
+            ..Limits::default()
+        };
+
+        let enabled = run(&site.join("index.mt"), limits, false)?;
+        let disabled = run(&site.join("index.mt"), limits, true)?;
+        let diamond = run(&site.join("0.mt"), limits, false)?;
+        let nested = run(&site.join("0.mt"), Limits { depth: 2, ..limits }, false)?;
+
+        std::fs::remove_dir_all(&directory)?;
+
+        assert_eq!(
+            enabled,
+            [
+                "0 body".to_string(),
+                "1 nav".to_string(),
+                "including '../index.mt' makes a cycle at \"../index.mt\" <- from [\"\\\"parts/nav.mt\\\" <-\"]".to_string(),
+                "2 a".to_string(),
+                format!("cannot include '../../secret.mt': the file is outside of '{}' at \"../../secret.mt\" <- from []", root.display()),
+            ]
+        );
+
+        assert_eq!(
+            disabled,
+            [
+                "0 body",
+                "cannot include 'parts/nav.mt': includes are turned off for this input at \"parts/nav.mt\" <- from []",
+            ]
+        );
+
+        assert_eq!(
+            diamond.last().map(String::as_str),
+            Some("the document includes more than 6 files at \"7.mt\" <- from []")
+        );
+
+        assert_eq!(
+            nested.last().map(String::as_str),
+            Some("this include is nested deeper than the limit of 2 levels at \"3.mt\" <- from []")
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,356 @@
+use std::rc::Rc;
+
+use iterator_stage::ConfigurableProcessor;
+
+use crate::analyzer::{
+    diagnostic::{self, Diagnostic, Position, Span},
+    source::Source,
+};
+
+use super::{Kind, Node, ProcessingInstruction, Result, Value};
+
+/// The largest document the pipeline accepts.
+///
+/// Every limit bounds the memory a hostile document can make the later stages
+/// use; going over one stops the analysis with a critical diagnostic.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Limits {
+    /// The most attributes a single element can have.
+    pub attributes: usize,
+    /// The most bytes the input can have.
+    pub bytes: usize,
+    /// The deepest a node can be nested, and an include.
+    pub depth: u16,
+    /// The most files a document can include, counting every include.
+    pub includes: usize,
+    /// The most bytes a name, literal or attribute value can have.
+    pub length: usize,
+    /// The most nodes the document can have.
+    pub nodes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            attributes: 256,
+            bytes: 16 * 1024 * 1024,
+            depth: 256,
+            includes: 1024,
+            length: 64 * 1024,
+            nodes: 1_000_000,
+        }
+    }
+}
+
+struct Frame {
+    attributes: usize,
+    depth: u16,
+    span: Span,
+}
+
+/// Stops the node stream at the first limit it goes over.
+pub struct Analyzer<T>
+where
+    T: Iterator<Item = Result>,
+{
+    finished: bool,
+    limits: Limits,
+    nodes: usize,
+    reader: T,
+    source: Option<Rc<Source>>,
+    stack: Vec<Frame>,
+}
+
+impl<T> Analyzer<T>
+where
+    T: Iterator<Item = Result>,
+{
+    fn stop(&mut self, message: &str, span: Span) -> Diagnostic {
+        self.finished = true;
+
+        Diagnostic::new(diagnostic::Kind::Critical, message, span)
+            .with_note("the analysis stopped here")
+    }
+
+    fn check_source(&mut self, source: Rc<Source>) -> Option<Result> {
+        if source.text.len() <= self.limits.bytes {
+            return None;
+        }
+
+        let message = format!(
+            "the input is {} bytes long, over the limit of {} bytes",
+            source.text.len(),
+            self.limits.bytes
+        );
+
+        let span = Span::new(source, Position::default(), Position::default());
+
+        Some(Result::from(self.stop(&message, span)))
+    }
+
+    fn check_attributes(&mut self, node: &Node) -> Option<Result> {
+        let attribute = matches!(node.kind, Kind::Attribute(_));
+
+        while self.stack.last().is_some_and(|frame| {
+            frame.depth > node.depth || frame.depth == node.depth && !attribute
+        }) {
+            self.stack.pop();
+        }
+
+        match &node.kind {
+            Kind::Attribute(_) => {
+                let frame = self
+                    .stack
+                    .last_mut()
+                    .filter(|frame| frame.depth == node.depth)?;
+
+                frame.attributes += 1;
+
+                if frame.attributes <= self.limits.attributes {
+                    return None;
+                }
+
+                let element = frame.span.clone();
+                let message = format!(
+                    "an element cannot have more than {} attributes",
+                    self.limits.attributes
+                );
+
+                let diagnostic = self
+                    .stop(&message, node.span.clone())
+                    .with_label(element, "the element is declared here");
+
+                Some(Result::from(diagnostic))
+            }
+            Kind::Element(_) => {
+                self.stack.push(Frame {
+                    attributes: 0,
+                    depth: node.depth,
+                    span: node.span.clone(),
+                });
+
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn check_length(&mut self, node: &Node) -> Option<Result> {
+        #[rustfmt::skip]
+        let values = match &node.kind {
+            Kind::Attribute(attribute) => vec![Some(attribute.name.as_str()), attribute.value.as_ref().and_then(Value::as_str)],
+            Kind::Comment(comment) => vec![Some(comment.value.as_str())],
+            Kind::Element(element) => vec![Some(element.name.as_str())],
+            Kind::ProcessingInstruction(ProcessingInstruction::Borrow(borrow)) => vec![Some(borrow.name.as_str())],
+            Kind::ProcessingInstruction(ProcessingInstruction::Borrowable(borrowable)) => vec![Some(borrowable.name.as_str())],
+            Kind::ProcessingInstruction(ProcessingInstruction::Include(include)) => vec![Some(include.path.as_str())],
+            Kind::Text(text) => vec![Some(text.value.as_str())],
+        };
+
+        let length = values.into_iter().flatten().map(str::len).max()?;
+
+        if length <= self.limits.length {
+            return None;
+        }
+
+        let message = format!(
+            "this {} is {} bytes long, over the limit of {} bytes",
+            node.kind, length, self.limits.length
+        );
+
+        Some(Result::from(self.stop(&message, node.span.clone())))
+    }
+
+    fn check(&mut self, node: &Node) -> Option<Result> {
+        self.nodes += 1;
+
+        if self.nodes > self.limits.nodes {
+            let message = format!("the document has more than {} nodes", self.limits.nodes);
+
+            return Some(Result::from(self.stop(&message, node.span.clone())));
+        }
+
+        if node.depth > self.limits.depth {
+            let message = format!(
+                "this {} is nested deeper than the limit of {} levels",
+                node.kind, self.limits.depth
+            );
+
+            return Some(Result::from(self.stop(&message, node.span.clone())));
+        }
+
+        self.check_length(node)
+            .or_else(|| self.check_attributes(node))
+    }
+}
+
+impl<T> Iterator for Analyzer<T>
+where
+    T: Iterator<Item = Result>,
+{
+    type Item = Result;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        if let Some(source) = self.source.take() {
+            if let Some(result) = self.check_source(source) {
+                return Some(result);
+            }
+        }
+
+        let result = self.reader.next()?;
+
+        match &result {
+            Result::Value(node) => {
+                if let Some(result) = self.check(node) {
+                    return Some(result);
+                }
+            }
+            // A limit the tokenizer or the reader went over stops the rest too.
+            Result::Diagnostic(diagnostic) => {
+                self.finished = matches!(diagnostic.kind, diagnostic::Kind::Critical);
+            }
+        }
+
+        Some(result)
+    }
+}
+
+impl<T> Analyzer<T>
+where
+    T: Iterator<Item = Result>,
+{
+    pub fn new(reader: T, source: Rc<Source>, limits: Limits) -> Self {
+        Self {
+            finished: false,
+            limits,
+            nodes: 0,
+            reader,
+            source: Some(source),
+            stack: Vec::new(),
+        }
+    }
+}
+
+pub struct Configurator(pub Rc<Source>, pub Limits);
+
+impl<T> ConfigurableProcessor<T> for Configurator
+where
+    T: Iterator<Item = Result>,
+{
+    type Iterator = Analyzer<T>;
+
+    fn build(self, upstream: T) -> Self::Iterator {
+        Self::Iterator::new(upstream, self.0, self.1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analyzer::{
+        diagnostic,
+        filesystem::{Error, FileReader},
+        lexical::tokenizer,
+        source::Source,
+        syntactic::{converter, Result},
+        Pipeline,
+    };
+
+    use super::{Analyzer, Limits};
+
+    fn run(text: &str, limits: Limits) -> Vec<Result> {
+        let source = Source::string("test.mt", text);
+        let tokens =
+            tokenizer::Analyzer::new(source.clone(), source.chars()).with_limit(limits.length);
+
+        Analyzer::new(converter::Analyzer::new(tokens), source, limits).collect()
+    }
+
+    fn critical(results: &[Result]) -> Option<&str> {
+        match results.last() {
+            Some(Result::Diagnostic(diagnostic))
+                if matches!(diagnostic.kind, diagnostic::Kind::Critical) =>
+            {
+                Some(&diagnostic.message)
+            }
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test() {
+        let limits = Limits {
+            attributes: 2,
+            bytes: 64,
+            depth: 2,
+            includes: 4,
+            length: 8,
+            nodes: 12,
+        };
+
+        let results = run("a { b { c } } p | x | y q | x | y | z", limits);
+
+        assert_eq!(results.len(), 10);
+        assert_eq!(
+            critical(&results),
+            Some("an element cannot have more than 2 attributes")
+        );
+
+        let results = run("a { b { c { d } } }", limits);
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(
+            critical(&results),
+            Some("this element is nested deeper than the limit of 2 levels")
+        );
+
+        let results = run("p { 'a long literal' }", limits);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            critical(&results),
+            Some("this token is longer than the limit of 8 bytes")
+        );
+
+        let results = run("a b c d e f g h i j k l m", limits);
+
+        assert_eq!(results.len(), 13);
+        assert_eq!(
+            critical(&results),
+            Some("the document has more than 12 nodes")
+        );
+
+        let results = run(&"a ".repeat(40), limits);
+
+        assert_eq!(results.len(), 1);
+        assert!(
+            critical(&results).is_some_and(|message| message.starts_with("the input is 80 bytes"))
+        );
+
+        let pipeline = Pipeline {
+            limits: Limits {
+                bytes: 1 << 20,
+                ..limits
+            },
+            ..Pipeline::new()
+        };
+
+        let source = Source::string("test.mt", "{".repeat(1 << 19));
+        let results: Vec<Result> = pipeline.analyze(&source).collect();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            critical(&results),
+            Some("this block is nested deeper than the limit of 2 levels")
+        );
+
+        let reader = FileReader::from_reader("abc\u{e9}".as_bytes()).with_limit(Some(4));
+        let chars: Vec<_> = reader.collect();
+
+        assert_eq!(chars.len(), 4);
+        assert!(matches!(chars.last(), Some(Err(Error::Io(_)))));
+    }
+}
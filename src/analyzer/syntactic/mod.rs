@@ -1,22 +1,40 @@
 pub mod converter;
+pub mod cst;
 pub mod desugaring;
 pub mod expansion;
+pub mod inclusion;
+pub mod limits;
+pub mod tree;
 
 use std::rc::Rc;
 
-use crate::analyzer::diagnostic::Span;
+use crate::analyzer::{diagnostic::Span, lexical::Token};
 
 use super::diagnostic::Diagnostic;
 
 #[derive(Clone, Debug)]
 pub struct Attribute {
     pub name: String,
-    pub value: Option<String>,
+    pub value: Option<Value>,
+    /// Whether the value is a raw or block literal, taken as it is written.
+    pub verbatim: bool,
 }
 
 impl Attribute {
-    pub fn new(name: String, value: Option<String>) -> Self {
-        Self { name, value }
+    pub fn new(name: String, value: Option<Value>) -> Self {
+        Self {
+            name,
+            value,
+            verbatim: false,
+        }
+    }
+
+    pub fn verbatim(name: String, value: Option<Value>) -> Self {
+        Self {
+            name,
+            value,
+            verbatim: true,
+        }
     }
 }
 
@@ -44,12 +62,31 @@ impl Borrowable {
 
 #[derive(Clone, Debug)]
 pub struct Comment {
+    /// Whether the comment is a `##` doc comment, which documents the element
+    /// that follows it.
+    pub doc: bool,
     pub value: String,
 }
 
 impl Comment {
     pub fn new(value: String) -> Self {
-        Self { value }
+        Self { doc: false, value }
+    }
+
+    pub fn doc(value: String) -> Self {
+        Self { doc: true, value }
+    }
+}
+
+/// A file spliced into the document, written `"header.mt" <-`.
+#[derive(Clone, Debug)]
+pub struct Include {
+    pub path: String,
+}
+
+impl Include {
+    pub fn new(path: String) -> Self {
+        Self { path }
     }
 }
 
@@ -68,6 +105,7 @@ impl Element {
 pub enum ProcessingInstruction {
     Borrow(Borrow),
     Borrowable(Borrowable),
+    Include(Include),
 }
 
 impl From<Borrow> for ProcessingInstruction {
@@ -82,14 +120,69 @@ impl From<Borrowable> for ProcessingInstruction {
     }
 }
 
+impl From<Include> for ProcessingInstruction {
+    fn from(value: Include) -> Self {
+        Self::Include(value)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Text {
     pub value: String,
+    /// Whether the text is a raw or block literal, taken as it is written.
+    pub verbatim: bool,
 }
 
 impl Text {
     pub fn new(value: String) -> Self {
-        Self { value }
+        Self {
+            value,
+            verbatim: false,
+        }
+    }
+
+    pub fn verbatim(value: String) -> Self {
+        Self {
+            value,
+            verbatim: true,
+        }
+    }
+}
+
+/// The value of an attribute, typed by the lexeme it was written with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Boolean(bool),
+    Float(f64),
+    Integer(i64),
+    Null,
+    String(String),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Null => write!(f, "null"),
+            Value::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
     }
 }
 
@@ -113,6 +206,7 @@ impl std::fmt::Display for Kind {
             Kind::ProcessingInstruction(kind) => match kind {
                 ProcessingInstruction::Borrow(_) => "borrow",
                 ProcessingInstruction::Borrowable(_) => "borrowable",
+                ProcessingInstruction::Include(_) => "include",
             },
             Kind::Text(_) => "text",
         };
@@ -165,6 +259,8 @@ pub struct Node {
     pub depth: u16,
     pub kind: Kind,
     pub span: Span,
+    /// The whitespace, newlines and delimiters written right before the node.
+    pub trivia: Vec<Token>,
 }
 
 impl Node {
@@ -173,8 +269,14 @@ impl Node {
         T: Into<Kind>,
     {
         let kind = kind.into();
-
-        Self { depth, kind, span }
+        let trivia = Vec::new();
+
+        Self {
+            depth,
+            kind,
+            span,
+            trivia,
+        }
     }
 }
 
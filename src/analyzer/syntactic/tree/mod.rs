@@ -0,0 +1,448 @@
+use std::rc::Rc;
+
+use crate::analyzer::diagnostic::{self, Diagnostic, Span};
+
+use super::{Kind, ProcessingInstruction, Result, Value};
+
+#[derive(Clone, Debug)]
+pub struct Attribute {
+    pub name: String,
+    pub span: Span,
+    pub value: Option<Value>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Comment {
+    pub span: Span,
+    pub value: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Element {
+    pub attributes: Vec<Attribute>,
+    pub children: Vec<Node>,
+    /// The `##` doc comments written right before the element, one per line.
+    pub doc: Option<String>,
+    pub name: String,
+    pub span: Span,
+}
+
+impl Element {
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Instruction {
+    pub children: Vec<Node>,
+    pub kind: ProcessingInstruction,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub struct Text {
+    pub span: Span,
+    pub value: String,
+}
+
+#[derive(Clone, Debug)]
+pub enum Node {
+    Comment(Comment),
+    Element(Element),
+    Instruction(Instruction),
+    Text(Text),
+}
+
+impl Node {
+    pub fn children(&self) -> &[Node] {
+        match self {
+            Node::Element(element) => &element.children,
+            Node::Instruction(instruction) => &instruction.children,
+            Node::Comment(_) | Node::Text(_) => &[],
+        }
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<Node>> {
+        match self {
+            Node::Element(element) => Some(&mut element.children),
+            Node::Instruction(instruction) => Some(&mut instruction.children),
+            Node::Comment(_) | Node::Text(_) => None,
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        match self {
+            Node::Comment(comment) => &comment.span,
+            Node::Element(element) => &element.span,
+            Node::Instruction(instruction) => &instruction.span,
+            Node::Text(text) => &text.span,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Document {
+    pub children: Vec<Node>,
+}
+
+impl Document {
+    pub fn cursor(&self) -> Option<Cursor<'_>> {
+        if self.children.is_empty() {
+            return None;
+        }
+
+        Some(Cursor {
+            document: self,
+            path: vec![0],
+        })
+    }
+}
+
+/// A position inside a [`Document`], addressed by the child index taken at every level.
+#[derive(Clone, Debug)]
+pub struct Cursor<'a> {
+    document: &'a Document,
+    path: Vec<usize>,
+}
+
+impl<'a> Cursor<'a> {
+    fn siblings(&self) -> &'a [Node] {
+        let mut siblings = self.document.children.as_slice();
+
+        for &index in &self.path[..self.path.len() - 1] {
+            siblings = siblings[index].children();
+        }
+
+        siblings
+    }
+
+    fn sibling(&self, index: usize) -> Option<Self> {
+        if index >= self.siblings().len() {
+            return None;
+        }
+
+        let mut path = self.path.clone();
+        *path.last_mut()? = index;
+
+        Some(Self {
+            document: self.document,
+            path,
+        })
+    }
+
+    pub fn node(&self) -> &'a Node {
+        &self.siblings()[self.index()]
+    }
+
+    pub fn depth(&self) -> usize {
+        self.path.len() - 1
+    }
+
+    pub fn index(&self) -> usize {
+        self.path[self.path.len() - 1]
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        if self.path.len() < 2 {
+            return None;
+        }
+
+        let path = self.path[..self.path.len() - 1].to_vec();
+
+        Some(Self {
+            document: self.document,
+            path,
+        })
+    }
+
+    pub fn first_child(&self) -> Option<Self> {
+        if self.node().children().is_empty() {
+            return None;
+        }
+
+        let mut path = self.path.clone();
+        path.push(0);
+
+        Some(Self {
+            document: self.document,
+            path,
+        })
+    }
+
+    pub fn next_sibling(&self) -> Option<Self> {
+        self.sibling(self.index() + 1)
+    }
+
+    pub fn previous_sibling(&self) -> Option<Self> {
+        self.sibling(self.index().checked_sub(1)?)
+    }
+}
+
+struct Frame {
+    depth: u32,
+    node: Node,
+}
+
+/// Assembles the flat, depth-tagged node stream into an owned [`Document`].
+///
+/// Every node at depth `n` becomes a child of the closest preceding element (or
+/// borrowable) at depth `n - 1`, while attributes are attached to the element
+/// that precedes them at their own depth. Doc comments are held back and
+/// attached to the element that follows them at their depth.
+pub struct Builder<I>
+where
+    I: Iterator<Item = Result>,
+{
+    children: Vec<Node>,
+    docs: Vec<(u32, Comment)>,
+    stack: Vec<Frame>,
+    trace: Vec<Diagnostic>,
+    upstream: I,
+}
+
+impl<I> Builder<I>
+where
+    I: Iterator<Item = Result>,
+{
+    fn child_depth(&self) -> u32 {
+        self.stack.last().map_or(0, |frame| frame.depth + 1)
+    }
+
+    fn close(&mut self) {
+        if let Some(frame) = self.stack.pop() {
+            self.append(frame.node);
+        }
+    }
+
+    fn close_until(&mut self, depth: u32) {
+        while self.child_depth() > depth {
+            self.close();
+        }
+    }
+
+    fn append(&mut self, node: Node) {
+        #[rustfmt::skip]
+        let children =
+        match self.stack.last_mut() {
+            Some(frame) => frame.node.children_mut(),
+            None => None,
+        };
+
+        children.unwrap_or(&mut self.children).push(node);
+    }
+
+    fn open(&mut self, depth: u32, node: Node) {
+        self.close_until(depth);
+        self.stack.push(Frame { depth, node });
+    }
+
+    /// Keeps the doc comments that document no element as plain comments.
+    fn release_docs(&mut self) {
+        for (depth, comment) in std::mem::take(&mut self.docs) {
+            let diagnostic = Diagnostic::new(
+                diagnostic::Kind::Warning,
+                "this doc comment documents no element",
+                comment.span.clone(),
+            )
+            .with_help("write it right before an element, or use `#` for a plain comment");
+
+            self.trace.push(diagnostic);
+
+            self.close_until(depth);
+            self.append(Node::Comment(comment));
+        }
+    }
+
+    fn take_docs(&mut self, depth: u32) -> Option<String> {
+        if self.docs.iter().any(|(doc, _)| *doc != depth) {
+            self.release_docs();
+        }
+
+        #[rustfmt::skip]
+        let lines: Vec<String> = std::mem::take(&mut self.docs).into_iter().map(|(_, comment)| comment.value).collect();
+
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+}
+
+impl<I> Builder<I>
+where
+    I: Iterator<Item = Result>,
+{
+    fn build_attribute(&mut self, depth: u32, attribute: Attribute) {
+        self.close_until(depth + 1);
+
+        if let Some(Frame {
+            node: Node::Element(element),
+            ..
+        }) = self.stack.last_mut()
+        {
+            return element.attributes.push(attribute);
+        }
+
+        let message = format!(
+            "the attribute '{}' does not belong to any element",
+            attribute.name
+        );
+
+        let diagnostic = Diagnostic::new(diagnostic::Kind::Error, &message, attribute.span);
+        self.trace.push(diagnostic);
+    }
+
+    fn build_node(&mut self, depth: u32, kind: Kind, span: Span) {
+        #[rustfmt::skip]
+        let node = match kind {
+            Kind::Attribute(attribute) => {
+                let name = attribute.name;
+                let value = attribute.value;
+
+                return self.build_attribute(depth, Attribute { name, span, value });
+            },
+            Kind::Comment(comment) if comment.doc => {
+                return self.docs.push((depth, Comment { span, value: comment.value }));
+            },
+            Kind::Comment(comment) => {
+                Node::Comment(Comment { span, value: comment.value })
+            },
+            Kind::Element(element) => {
+                let attributes = Vec::new();
+                let children = Vec::new();
+                let doc = self.take_docs(depth);
+
+                let node = Node::Element(Element { attributes, children, doc, name: element.name, span });
+
+                return self.open(depth, node);
+            },
+            Kind::ProcessingInstruction(kind) => {
+                let borrowable = matches!(kind, ProcessingInstruction::Borrowable(_));
+                let node = Node::Instruction(Instruction { children: Vec::new(), kind, span });
+
+                if borrowable {
+                    return self.open(depth, node);
+                }
+
+                node
+            },
+            Kind::Text(text) => {
+                Node::Text(Text { span, value: text.value })
+            },
+        };
+
+        self.release_docs();
+        self.close_until(depth);
+        self.append(node);
+    }
+
+    pub fn build(mut self) -> (Document, Vec<Diagnostic>) {
+        while let Some(result) = self.upstream.next() {
+            match result {
+                Result::Diagnostic(diagnostic) => {
+                    self.trace.push(diagnostic);
+                }
+                Result::Value(node) => {
+                    let node = Rc::unwrap_or_clone(node);
+
+                    self.build_node(node.depth.into(), node.kind, node.span);
+                }
+            }
+        }
+
+        self.release_docs();
+
+        while !self.stack.is_empty() {
+            self.close();
+        }
+
+        let document = Document {
+            children: self.children,
+        };
+
+        (document, self.trace)
+    }
+}
+
+impl<I> Builder<I>
+where
+    I: Iterator<Item = Result>,
+{
+    pub fn new(upstream: I) -> Self {
+        Self {
+            children: Vec::new(),
+            docs: Vec::new(),
+            stack: Vec::new(),
+            trace: Vec::new(),
+            upstream,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analyzer::{
+        lexical::tokenizer,
+        source::Source,
+        syntactic::{converter, tree::Node, Value},
+    };
+
+    use super::Builder;
+
+    #[test]
+    fn test() {
+        let source = Source::string(
+            "test.mt",
+            "html { head | lang \"en\" { title \"x\" } body { # note\n p } }",
+        );
+
+        let tokens = tokenizer::Analyzer::new(source.clone(), source.chars());
+        let (document, diagnostics) = Builder::new(converter::Analyzer::new(tokens)).build();
+
+        assert!(diagnostics.is_empty());
+
+        let html = document.cursor().unwrap();
+        let head = html.first_child().unwrap();
+        let body = head.next_sibling().unwrap();
+
+        let Node::Element(element) = head.node() else {
+            panic!("expected an element");
+        };
+
+        assert_eq!(element.name, "head");
+        assert_eq!(
+            element.attribute("lang").unwrap().value,
+            Some(Value::from("en"))
+        );
+        assert_eq!(
+            body.first_child().unwrap().next_sibling().unwrap().depth(),
+            2
+        );
+        assert_eq!(body.parent().unwrap().index(), html.index());
+        assert!(body.previous_sibling().is_some());
+        assert!(html.next_sibling().is_none());
+
+        let source = Source::string(
+            "test.mt",
+            "## The page.\n## Second line.\nmain { ## Dangling.\n } ## Also dangling.",
+        );
+
+        let tokens = tokenizer::Analyzer::new(source.clone(), source.chars());
+        let (document, diagnostics) = Builder::new(converter::Analyzer::new(tokens)).build();
+
+        let Some(Node::Element(main)) = document.children.first() else {
+            panic!("expected an element");
+        };
+
+        assert_eq!(main.doc.as_deref(), Some("The page.\nSecond line."));
+        assert!(
+            matches!(&main.children[..], [Node::Comment(comment)] if comment.value == "Dangling.")
+        );
+        assert!(matches!(document.children.get(1), Some(Node::Comment(_))));
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "this doc comment documents no element"
+        );
+    }
+}
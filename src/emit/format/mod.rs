@@ -0,0 +1,345 @@
+use std::rc::Rc;
+
+use crate::analyzer::{
+    diagnostic::Diagnostic,
+    lexical::{tokenizer, Delimiter, Kind, Lexeme, Operator, Token},
+    source::Source,
+    syntactic::{converter, Result},
+};
+
+const INDENTATION: &str = "    ";
+
+/// The source text of a token; single quoted literals are written with double
+/// quotes when that needs no escaping.
+fn text(token: &Token) -> String {
+    let text = token.span.text();
+
+    match text
+        .strip_prefix('\'')
+        .and_then(|text| text.strip_suffix('\''))
+    {
+        Some(inner) if !inner.contains(['"', '\'', '\\']) => format!("\"{}\"", inner),
+        _ => text.to_string(),
+    }
+}
+
+/// Lays out the tokens of one document, one line at a time.
+struct Layout<'a> {
+    depth: usize,
+    indent: usize,
+    index: usize,
+    line: String,
+    output: String,
+    row: Option<usize>,
+    tokens: &'a [Token],
+    width: usize,
+}
+
+impl<'a> Layout<'a> {
+    fn peek(&self, offset: usize) -> Option<&'a Kind> {
+        self.tokens
+            .get(self.index + offset)
+            .map(|token| &token.kind)
+    }
+
+    fn advance(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.index)?;
+
+        self.index += 1;
+        self.row = Some(token.span.end.row);
+
+        Some(token)
+    }
+
+    fn flush(&mut self) {
+        if self.line.is_empty() {
+            return;
+        }
+
+        self.output.push_str(&INDENTATION.repeat(self.indent));
+        self.output.push_str(&self.line);
+        self.output.push('\n');
+
+        self.line.clear();
+    }
+
+    /// Starts the line of `token`, keeping one blank line where the source
+    /// had at least one, except right inside a block.
+    fn start(&mut self, token: &Token, depth: usize) {
+        let opened = self.line.ends_with('{');
+
+        self.flush();
+
+        let closing = token.kind == Kind::Delimiter(Delimiter::RightBrace);
+        let gap = self.row.is_some_and(|row| token.span.start.row > row + 1);
+
+        if gap && !opened && !closing && !self.output.is_empty() {
+            self.output.push('\n');
+        }
+
+        self.indent = depth;
+    }
+
+    fn width(&self, text: &str) -> usize {
+        self.indent * INDENTATION.len() + self.line.chars().count() + text.chars().count()
+    }
+}
+
+impl<'a> Layout<'a> {
+    /// Reads `| name "value"` groups until the next token that is not a pipe.
+    fn attributes(&mut self) -> Vec<String> {
+        let mut attributes = Vec::new();
+
+        while let Some(Kind::Operator(Operator::Pipe)) = self.peek(0) {
+            self.advance();
+
+            let mut attribute = "|".to_string();
+
+            if let Some(Kind::Lexeme(Lexeme::Identifier(_) | Lexeme::Literal(_))) = self.peek(0) {
+                if let Some(name) = self.advance() {
+                    attribute.push(' ');
+                    attribute.push_str(&text(name));
+                }
+
+                if let Some(Kind::Lexeme(
+                    Lexeme::Boolean(_)
+                    | Lexeme::Float(_)
+                    | Lexeme::Integer(_)
+                    | Lexeme::Literal(_)
+                    | Lexeme::Null,
+                )) = self.peek(0)
+                {
+                    if let Some(value) = self.advance() {
+                        attribute.push(' ');
+                        attribute.push_str(&text(value));
+                    }
+                }
+            }
+
+            attributes.push(attribute);
+        }
+
+        attributes
+    }
+
+    /// Writes an element, text, borrow or borrowable with its attributes, on
+    /// one line when it fits and with one attribute per line otherwise.
+    fn entry(&mut self, token: &Token) {
+        self.start(token, self.depth);
+        self.advance();
+
+        self.line.push_str(&text(token));
+
+        if let Some(Kind::Operator(operator @ (Operator::Borrow | Operator::Borrowable))) =
+            self.peek(0)
+        {
+            #[rustfmt::skip]
+            self.line.push_str(if *operator == Operator::Borrow { " <-" } else { " ->" });
+            self.advance();
+        }
+
+        self.pipes();
+    }
+
+    fn pipes(&mut self) {
+        let attributes = self.attributes();
+
+        if attributes.is_empty() {
+            return;
+        }
+
+        let single = format!(" {}", attributes.join(" "));
+        let brace = matches!(self.peek(0), Some(Kind::Delimiter(Delimiter::LeftBrace)));
+        let single_width = self.width(&single) + if brace { 2 } else { 0 };
+
+        if single_width <= self.width || self.line.is_empty() && attributes.len() == 1 {
+            if !self.line.is_empty() {
+                self.line.push_str(&single);
+            } else {
+                self.line.push_str(single.trim_start());
+            }
+
+            return;
+        }
+
+        for attribute in attributes {
+            if !self.line.is_empty() {
+                self.flush();
+                self.indent = self.depth + 1;
+            }
+
+            self.line.push_str(&attribute);
+        }
+    }
+
+    fn open(&mut self, token: &Token) {
+        self.advance();
+
+        let literal = self.tokens.get(self.index);
+
+        #[rustfmt::skip]
+        let inline = match (self.peek(0), self.peek(1)) {
+            (Some(Kind::Delimiter(Delimiter::RightBrace)), _) => Some(" {}".to_string()),
+            (Some(Kind::Lexeme(Lexeme::Literal(_))), Some(Kind::Delimiter(Delimiter::RightBrace))) => {
+                literal.map(|literal| format!(" {{ {} }}", text(literal))).filter(|inline| !inline.contains('\n'))
+            },
+            _ => None,
+        };
+
+        #[rustfmt::skip]
+        let inline = inline.filter(|inline| !self.line.is_empty() && self.width(inline) <= self.width);
+
+        if let Some(inline) = inline {
+            self.line.push_str(&inline);
+
+            while self.peek(0) != Some(&Kind::Delimiter(Delimiter::RightBrace)) {
+                self.advance();
+            }
+
+            self.advance();
+
+            return;
+        }
+
+        if self.line.is_empty() {
+            self.start(token, self.depth);
+            self.line.push('{');
+        } else {
+            self.line.push_str(" {");
+        }
+
+        self.depth += 1;
+    }
+
+    fn close(&mut self, token: &Token) {
+        self.depth = self.depth.saturating_sub(1);
+
+        self.start(token, self.depth);
+        self.advance();
+
+        self.line.push('}');
+    }
+
+    fn comment(&mut self, token: &Token) {
+        let trailing = self.row == Some(token.span.start.row) && !self.line.is_empty();
+
+        if trailing {
+            self.line.push(' ');
+        } else {
+            self.start(token, self.depth);
+        }
+
+        self.advance();
+
+        self.line.push_str(text(token).trim_end());
+
+        self.flush();
+    }
+
+    fn layout(mut self) -> String {
+        while let Some(token) = self.tokens.get(self.index) {
+            match &token.kind {
+                Kind::Delimiter(Delimiter::LeftBrace) => self.open(token),
+                Kind::Delimiter(Delimiter::RightBrace) => self.close(token),
+                Kind::Delimiter(Delimiter::Semicolon) => {
+                    self.advance();
+                    self.line.push(';');
+                }
+                Kind::Lexeme(Lexeme::Comment(_)) => self.comment(token),
+                Kind::Operator(Operator::Pipe) => {
+                    self.start(token, self.depth);
+                    self.pipes();
+                }
+                Kind::Trivia(_) => {
+                    self.advance();
+                }
+                Kind::Lexeme(_) | Kind::Operator(_) | Kind::Unknown(_) => self.entry(token),
+            }
+        }
+
+        self.flush();
+        self.output
+    }
+}
+
+/// Rewrites a document in the canonical layout: one entry per line indented
+/// by depth, attributes written as `| name "value"` after their element (one
+/// per line when they do not fit the width), blank lines collapsed to one and
+/// `#` comments kept where they were.
+///
+/// Only whitespace and the quotes of literals change, so the formatted
+/// document is analyzed exactly like the original one.
+pub struct Formatter {
+    width: usize,
+}
+
+impl Formatter {
+    /// Formats `source`, or returns the syntax errors that prevent it.
+    pub fn format(&self, source: &Rc<Source>) -> std::result::Result<String, Vec<Diagnostic>> {
+        let tokens: Vec<Token> = tokenizer::Analyzer::new(source.clone(), source.chars()).collect();
+
+        #[rustfmt::skip]
+        let trace: Vec<Diagnostic> = converter::Analyzer::new(tokens.iter().cloned()).filter_map(|result| match result {
+            Result::Diagnostic(diagnostic) => Some(diagnostic),
+            Result::Value(_) => None,
+        }).collect();
+
+        if !trace.is_empty() {
+            return Err(trace);
+        }
+
+        let layout = Layout {
+            depth: 0,
+            indent: 0,
+            index: 0,
+            line: String::new(),
+            output: String::new(),
+            row: None,
+            tokens: &tokens,
+            width: self.width,
+        };
+
+        Ok(layout.layout())
+    }
+}
+
+impl Formatter {
+    pub fn new(width: usize) -> Self {
+        Self { width }
+    }
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Self::new(80)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analyzer::source::Source;
+
+    use super::Formatter;
+
+    #[test]
+    fn test() {
+        let source = Source::string(
+            "test.mt",
+            "html{head{title{'Hi'}}   # the head\n\n\n\nbody|class 'a'|hidden{\n# nav\nnav <-\np|title \"a long title\"|lang \"en\"|dir \"ltr\"{\"text\"}}}",
+        );
+
+        let formatted = Formatter::new(40).format(&source).unwrap_or_default();
+
+        assert_eq!(
+            formatted,
+            "html {\n    head {\n        title { \"Hi\" }\n    } # the head\n\n    body | class \"a\" | hidden {\n        # nav\n        nav <-\n        p\n            | title \"a long title\"\n            | lang \"en\"\n            | dir \"ltr\" { \"text\" }\n    }\n}\n"
+        );
+
+        let source = Source::string("test.mt", &formatted);
+
+        assert_eq!(Formatter::new(40).format(&source).ok(), Some(formatted));
+        assert!(Formatter::default()
+            .format(&Source::string("test.mt", "a }"))
+            .is_err());
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+
+use crate::analyzer::{
+    diagnostic::{self, Diagnostic},
+    syntactic::{
+        tree::{Attribute, Document, Element, Instruction, Node, Text},
+        ProcessingInstruction, Value,
+    },
+};
+
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+fn is_valid_attribute_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|char| {
+            !char.is_whitespace()
+                && !char.is_control()
+                && !matches!(char, '"' | '\'' | '>' | '/' | '=' | '<')
+        })
+}
+
+fn escape_text(value: &str, output: &mut String) {
+    for char in value.chars() {
+        #[rustfmt::skip]
+        match char {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '\u{A0}' => output.push_str("&nbsp;"),
+            _ => output.push(char),
+        };
+    }
+}
+
+fn escape_attribute(value: &str, output: &mut String) {
+    for char in value.chars() {
+        #[rustfmt::skip]
+        match char {
+            '&' => output.push_str("&amp;"),
+            '"' => output.push_str("&quot;"),
+            '\u{A0}' => output.push_str("&nbsp;"),
+            _ => output.push(char),
+        };
+    }
+}
+
+/// Renders a [`Document`] as HTML5.
+///
+/// Borrowable blocks are not written where they are declared; each of their
+/// elements is written instead at the place where it is borrowed with `name <-`.
+pub struct Emitter<'a> {
+    comments: bool,
+    output: String,
+    scope: Vec<HashMap<&'a str, &'a Element>>,
+    trace: Vec<Diagnostic>,
+}
+
+impl<'a> Emitter<'a> {
+    fn warn(&mut self, message: &str, span: &diagnostic::Span) {
+        let diagnostic = Diagnostic::new(diagnostic::Kind::Warning, message, span.clone());
+
+        self.trace.push(diagnostic);
+    }
+
+    fn lookup(&self, name: &str) -> Option<&'a Element> {
+        self.scope
+            .iter()
+            .rev()
+            .find_map(|borrowables| borrowables.get(name).copied())
+    }
+}
+
+impl<'a> Emitter<'a> {
+    fn emit_attribute(&mut self, attribute: &Attribute) {
+        if !is_valid_attribute_name(&attribute.name) {
+            let message = format!(
+                "the attribute '{}' is not a valid HTML attribute name and was skipped",
+                attribute.name
+            );
+
+            return self.warn(&message, &attribute.span);
+        }
+
+        #[rustfmt::skip]
+        let value = match &attribute.value {
+            Some(Value::Null | Value::Boolean(false)) => return,
+            Some(Value::Boolean(true)) | None => None,
+            Some(value) => Some(value.to_string()),
+        };
+
+        self.output.push(' ');
+        self.output.push_str(&attribute.name);
+
+        if let Some(value) = &value {
+            self.output.push_str("=\"");
+            escape_attribute(value, &mut self.output);
+            self.output.push('"');
+        }
+    }
+
+    fn emit_comment(&mut self, comment: &str) {
+        if !self.comments {
+            return;
+        }
+
+        let mut value = String::new();
+
+        for char in comment.chars() {
+            if char == '-' && value.ends_with('-') {
+                value.push(' ');
+            }
+
+            value.push(char);
+        }
+
+        if value.starts_with('>') || value.starts_with("->") {
+            value.insert(0, ' ');
+        }
+
+        if value.ends_with('-') {
+            value.push(' ');
+        }
+
+        self.output.push_str("<!--");
+        self.output.push_str(&value);
+        self.output.push_str("-->");
+    }
+
+    fn emit_raw_text(&mut self, element: &Element) {
+        let closing = format!("</{}", element.name.to_ascii_lowercase());
+
+        for child in &element.children {
+            match child {
+                Node::Text(text) if text.value.to_ascii_lowercase().contains(&closing) => {
+                    let message = format!(
+                        "the text contains '{}' and would end the '{}' element early",
+                        closing, element.name
+                    );
+
+                    self.warn(&message, &text.span);
+                }
+                Node::Text(text) => {
+                    self.output.push_str(&text.value);
+                }
+                _ => {
+                    let message = format!("the '{}' element can only hold text", element.name);
+
+                    self.warn(&message, child.span());
+                }
+            }
+        }
+    }
+
+    fn emit_element(&mut self, element: &'a Element) {
+        let name = element.name.to_ascii_lowercase();
+
+        if let Some(doc) = &element.doc {
+            self.emit_comment(doc);
+        }
+
+        self.output.push('<');
+        self.output.push_str(&name);
+
+        for attribute in &element.attributes {
+            self.emit_attribute(attribute);
+        }
+
+        self.output.push('>');
+
+        if VOID_ELEMENTS.contains(&name.as_str()) {
+            if !element.children.is_empty() {
+                let message = format!("the void element '{}' cannot have children", name);
+
+                self.warn(&message, &element.span);
+            }
+
+            return;
+        }
+
+        if RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+            self.emit_raw_text(element);
+        } else {
+            self.emit_nodes(&element.children);
+        }
+
+        self.output.push_str("</");
+        self.output.push_str(&name);
+        self.output.push('>');
+    }
+
+    fn emit_instruction(&mut self, instruction: &Instruction) {
+        if let ProcessingInstruction::Borrow(borrow) = &instruction.kind {
+            if let Some(element) = self.lookup(&borrow.name) {
+                self.emit_element(element);
+            }
+        }
+    }
+
+    fn emit_text(&mut self, text: &Text) {
+        escape_text(&text.value, &mut self.output);
+    }
+
+    fn emit_nodes(&mut self, nodes: &'a [Node]) {
+        let mut borrowables = HashMap::new();
+
+        for node in nodes {
+            if let Node::Instruction(Instruction {
+                children,
+                kind: ProcessingInstruction::Borrowable(_),
+                ..
+            }) = node
+            {
+                for child in children {
+                    if let Node::Element(element) = child {
+                        borrowables.insert(element.name.as_str(), element);
+                    }
+                }
+            }
+        }
+
+        self.scope.push(borrowables);
+
+        for node in nodes {
+            #[rustfmt::skip]
+            match node {
+                Node::Comment(comment) => self.emit_comment(&comment.value),
+                Node::Element(element) => self.emit_element(element),
+                Node::Instruction(instruction) => self.emit_instruction(instruction),
+                Node::Text(text) => self.emit_text(text),
+            };
+        }
+
+        self.scope.pop();
+    }
+
+    pub fn emit(mut self, document: &'a Document) -> (String, Vec<Diagnostic>) {
+        let html = document.children.iter().any(|node| {
+            matches!(node, Node::Element(element) if element.name.eq_ignore_ascii_case("html"))
+        });
+
+        if html {
+            self.output.push_str("<!DOCTYPE html>");
+        }
+
+        self.emit_nodes(&document.children);
+
+        (self.output, self.trace)
+    }
+}
+
+impl<'a> Emitter<'a> {
+    pub fn new() -> Self {
+        Self {
+            comments: true,
+            output: String::new(),
+            scope: Vec::new(),
+            trace: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Emitter<'a> {
+    /// Whether comments, doc comments included, are kept in the output.
+    pub fn with_comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        self
+    }
+}
+
+impl<'a> Default for Emitter<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analyzer::{
+        lexical::tokenizer,
+        source::Source,
+        syntactic::{converter, tree::Builder},
+    };
+
+    use super::Emitter;
+
+    fn render(text: &str) -> String {
+        render_with_comments(text, true)
+    }
+
+    fn render_with_comments(text: &str, comments: bool) -> String {
+        let source = Source::string("test.mt", text);
+
+        let tokens = tokenizer::Analyzer::new(source.clone(), source.chars());
+        let (document, _) = Builder::new(converter::Analyzer::new(tokens)).build();
+
+        Emitter::new().with_comments(comments).emit(&document).0
+    }
+
+    #[test]
+    fn test() {
+        let output =
+            render(r#"html { body { p | title 'a "b" & c' { "1 < 2" } br input | disabled } }"#);
+
+        assert_eq!(
+            output,
+            "<!DOCTYPE html><html><body><p title=\"a &quot;b&quot; &amp; c\">1 &lt; 2</p><br><input disabled></body></html>"
+        );
+
+        let output = render(r#"layout -> { nav { "x" } } main { nav <- }"#);
+
+        assert_eq!(output, "<main><nav>x</nav></main>");
+
+        let output =
+            render("input | required true | hidden false | title null | step 0.5 | max -10");
+
+        assert_eq!(output, "<input required step=\"0.5\" max=\"-10\">");
+
+        let output = render("## The greeting.\np { #* hidden *# 'hi' }");
+
+        assert_eq!(output, "<!--The greeting.--><p><!--#* hidden *#-->hi</p>");
+
+        let output = render_with_comments("## The greeting.\np { # note\n 'hi' }", false);
+
+        assert_eq!(output, "<p>hi</p>");
+
+        let output = render("# --->x\n# --!>y\n## ->z-\np");
+
+        assert_eq!(
+            output,
+            "<!--# - - ->x--><!--# - -!>y--><!-- ->z- --><p></p>"
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{
+    analyzer::{
+        diagnostic::{sarif, Diagnostic},
+        source::Source,
+        syntactic::{cst::SyntaxTree, tree::Builder},
+        Pipeline,
+    },
+    emit::{format::Formatter, html},
+};
+
+/// Runs arbitrary bytes through every stage the way the command line does:
+/// decoding, analysis, tree building, HTML, formatting, the lossless syntax
+/// tree and every diagnostic format. Includes are turned off, as for the
+/// standard input, so a run never reads the filesystem.
+///
+/// Bad input must only ever produce diagnostics, so this is the entry point of
+/// the fuzz targets and of the regression corpus; a panic here is a bug.
+pub fn run(bytes: &[u8]) {
+    let Ok((source, mut trace)) = Source::bytes("fuzz.mt", bytes) else {
+        return;
+    };
+
+    let pipeline = Pipeline {
+        includes: false,
+        ..Pipeline::new()
+    };
+
+    let (document, diagnostics) = Builder::new(pipeline.analyze(&source)).build();
+    trace.extend(diagnostics);
+
+    let (_, diagnostics) = html::Emitter::new().emit(&document);
+    trace.extend(diagnostics);
+
+    if let Err(diagnostics) = Formatter::default().format(&source) {
+        trace.extend(diagnostics);
+    }
+
+    let (tree, diagnostics) = SyntaxTree::parse(&source);
+    trace.extend(diagnostics);
+
+    assert_eq!(tree.text(), source.text, "the syntax tree is not lossless");
+
+    for diagnostic in &trace {
+        render(diagnostic);
+    }
+
+    sarif::log(&trace).to_string();
+}
+
+fn render(diagnostic: &Diagnostic) {
+    diagnostic.to_string();
+    diagnostic.plain().to_string();
+
+    let _ = serde_json::to_string(diagnostic);
+}
+
+#[cfg(test)]
+mod test {
+    #[rustfmt::skip]
+    const FRAGMENTS: [&[u8]; 34] = [
+        b"{", b"}", b";", b"|", b"<-", b"->", b"<", b"-", b"#", b"'", b"\"", b"\\", b"\n", b"\r",
+        b" ", b"\t", b"a", b"div", b"class", b"\"x\"", "\u{e9}".as_bytes(), b"\0", b"\xff", b"\xe0\x80",
+        b"r#", b"\"\"\"", b"\\u{", b"#*", b"*#", b"##", b"component", b"A", b"let", b"\"{x}\"",
+    ];
+
+    /// A xorshift generator, so every run tries the same inputs.
+    fn next(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+
+        *state
+    }
+
+    #[test]
+    fn test() -> std::io::Result<()> {
+        let corpus = concat!(env!("CARGO_MANIFEST_DIR"), "/fuzz/corpus/pipeline");
+        let mut count = 0;
+
+        for entry in std::fs::read_dir(corpus)? {
+            let bytes = std::fs::read(entry?.path())?;
+
+            super::run(&bytes);
+            count += 1;
+        }
+
+        assert!(count > 0, "the corpus at {} is empty", corpus);
+
+        let mut state = 0x2545_f491_4f6c_dd1d;
+
+        for _ in 0..2000 {
+            let length = next(&mut state) % 48;
+            let mut bytes = Vec::new();
+
+            for _ in 0..length {
+                let fragment = FRAGMENTS[(next(&mut state) % FRAGMENTS.len() as u64) as usize];
+
+                bytes.extend_from_slice(fragment);
+            }
+
+            super::run(&bytes);
+        }
+
+        Ok(())
+    }
+}
@@ -1 +1,5 @@
 pub mod analyzer;
+pub mod emit;
+#[cfg(any(test, feature = "fuzz"))]
+pub mod fuzz;
+pub mod lsp;
@@ -0,0 +1,786 @@
+pub mod transport;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, BufRead, Write},
+    rc::Rc,
+};
+
+use serde_json::{json, Value};
+
+use crate::analyzer::{
+    diagnostic::{self, Diagnostic, Position, Span},
+    semantic::schema::{self, Merge, Schema},
+    source::Source,
+    syntactic::{
+        tree::{Attribute, Builder, Document, Element, Instruction, Node},
+        ProcessingInstruction,
+    },
+    Pipeline,
+};
+
+use self::transport::Transport;
+
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const PARSE_ERROR: i64 = -32700;
+
+const SYMBOL_NAMESPACE: u8 = 3;
+const SYMBOL_PROPERTY: u8 = 7;
+const SYMBOL_STRUCT: u8 = 23;
+
+/// The URI of a source: the one the editor named a document with, or a
+/// `file://` URI for a file it includes.
+fn uri(source: &Source) -> String {
+    source.uri().unwrap_or_else(|| source.name.clone())
+}
+
+/// Converts a position to the protocol, whose characters count UTF-16 units.
+fn position(source: &Source, position: Position) -> Value {
+    let line = source.line(position.row).unwrap_or_default();
+    let character: usize = line.chars().take(position.col).map(char::len_utf16).sum();
+
+    json!({ "character": character, "line": position.row })
+}
+
+fn range(span: &Span) -> Value {
+    json!({
+        "end": position(&span.source, span.end),
+        "start": position(&span.source, span.start),
+    })
+}
+
+fn severity(kind: diagnostic::Kind) -> u8 {
+    match kind {
+        diagnostic::Kind::Critical | diagnostic::Kind::Error => 1,
+        diagnostic::Kind::Warning => 2,
+        diagnostic::Kind::Debug => 3,
+    }
+}
+
+fn diagnostic(diagnostic: &Diagnostic) -> Value {
+    let mut message = diagnostic.message.clone();
+
+    for note in &diagnostic.notes {
+        message.push_str(&format!("\nnote: {}", note));
+    }
+
+    for help in &diagnostic.help {
+        message.push_str(&format!("\nhelp: {}", help));
+    }
+
+    #[rustfmt::skip]
+    let related: Vec<Value> = diagnostic.labels.iter().map(|label| json!({
+        "location": { "range": range(&label.span), "uri": uri(&label.span.source) },
+        "message": label.message,
+    })).collect();
+
+    json!({
+        "message": message,
+        "range": range(&diagnostic.span),
+        "relatedInformation": related,
+        "severity": severity(diagnostic.kind),
+        "source": env!("CARGO_PKG_NAME"),
+    })
+}
+
+/// A position in a document, as a row and a column in characters.
+struct At {
+    col: usize,
+    row: usize,
+    source: Rc<Source>,
+}
+
+impl At {
+    /// Converts the `position` of a request, clamping it to its line.
+    fn locate(source: Rc<Source>, position: &Value) -> Option<Self> {
+        let row = usize::try_from(position["line"].as_u64()?).ok()?;
+        let character = usize::try_from(position["character"].as_u64()?).ok()?;
+
+        let mut units = 0;
+        let mut col = 0;
+
+        for char in source.line(row)?.chars() {
+            if units >= character {
+                break;
+            }
+
+            units += char.len_utf16();
+            col += 1;
+        }
+
+        Some(Self { col, row, source })
+    }
+
+    fn within(&self, span: &Span) -> bool {
+        let at = (self.row, self.col);
+
+        Rc::ptr_eq(&self.source, &span.source)
+            && (span.start.row, span.start.col) <= at
+            && at <= (span.end.row, span.end.col)
+    }
+}
+
+/// Finds the element the borrow under `at` refers to, looking through the
+/// borrowables in scope the way the HTML emitter does.
+fn resolve<'a>(
+    nodes: &'a [Node],
+    at: &At,
+    scope: &mut Vec<HashMap<&'a str, &'a Element>>,
+) -> Option<&'a Element> {
+    let mut borrowables = HashMap::new();
+
+    for node in nodes {
+        if let Node::Instruction(Instruction {
+            children,
+            kind: ProcessingInstruction::Borrowable(_),
+            ..
+        }) = node
+        {
+            for child in children {
+                if let Node::Element(element) = child {
+                    borrowables.insert(element.name.as_str(), element);
+                }
+            }
+        }
+    }
+
+    scope.push(borrowables);
+
+    let mut found = None;
+
+    for node in nodes {
+        if let Node::Instruction(Instruction {
+            kind: ProcessingInstruction::Borrow(borrow),
+            span,
+            ..
+        }) = node
+        {
+            if at.within(span) {
+                #[rustfmt::skip]
+                let element = scope.iter().rev().find_map(|borrowables| borrowables.get(borrow.name.as_str()));
+
+                found = element.copied();
+
+                break;
+            }
+        }
+
+        found = resolve(node.children(), at, scope);
+
+        if found.is_some() {
+            break;
+        }
+    }
+
+    scope.pop();
+
+    found
+}
+
+/// What a hover request points at.
+enum Target<'a> {
+    Attribute(&'a Element, &'a Attribute),
+    Element(&'a str, Option<&'a str>, &'a Span),
+}
+
+fn target<'a>(nodes: &'a [Node], at: &At) -> Option<Target<'a>> {
+    for node in nodes {
+        match node {
+            Node::Element(element) => {
+                if at.within(&element.span) {
+                    let doc = element.doc.as_deref();
+
+                    return Some(Target::Element(&element.name, doc, &element.span));
+                }
+
+                for attribute in &element.attributes {
+                    if at.within(&attribute.span) {
+                        return Some(Target::Attribute(element, attribute));
+                    }
+                }
+            }
+            Node::Instruction(Instruction {
+                kind: ProcessingInstruction::Borrow(borrow),
+                span,
+                ..
+            }) if at.within(span) => {
+                return Some(Target::Element(&borrow.name, None, span));
+            }
+            _ => {}
+        }
+
+        if let Some(target) = target(node.children(), at) {
+            return Some(target);
+        }
+    }
+
+    None
+}
+
+fn describe_attribute(attribute: &schema::Attribute) -> String {
+    let mut description = attribute.value.to_string();
+
+    if attribute.required {
+        description.push_str(", required");
+    }
+
+    if let Some(default) = &attribute.default {
+        description.push_str(&format!(", default '{}'", default));
+    }
+
+    if let Merge::Join(separator) = &attribute.merge {
+        description.push_str(&format!(", joined with '{}'", separator));
+    }
+
+    description
+}
+
+fn describe_element(schema: &Schema, name: &str) -> String {
+    let Some(element) = schema.element(name) else {
+        return format!("**element** `{}`\n\nnot declared in the schema", name);
+    };
+
+    let quote = |names: &[String]| {
+        #[rustfmt::skip]
+        let names: Vec<String> = names.iter().map(|name| format!("`{}`", name)).collect();
+
+        names.join(", ")
+    };
+
+    let mut lines = vec![format!("**element** `{}`\n", name)];
+
+    lines.push(match element.text {
+        true => "- holds text".to_string(),
+        false => "- cannot hold text".to_string(),
+    });
+
+    if element.open {
+        lines.push("- children: any declared element".to_string());
+    } else if !element.children.is_empty() {
+        lines.push(format!("- children: {}", quote(&element.children)));
+    }
+
+    if !element.required.is_empty() {
+        lines.push(format!("- required children: {}", quote(&element.required)));
+    }
+
+    #[rustfmt::skip]
+    let attributes: Vec<String> = schema.attributes(element).map(|attribute| {
+        format!("`{}` ({})", attribute.name, describe_attribute(attribute))
+    }).collect();
+
+    if !attributes.is_empty() {
+        lines.push(format!("- attributes: {}", attributes.join(", ")));
+    }
+
+    lines.join("\n")
+}
+
+/// The span of a node together with its attributes and descendants.
+fn extent(node: &Node) -> Span {
+    let mut span = node.span().clone();
+
+    let mut extend = |other: &Span| {
+        if Rc::ptr_eq(&other.source, &span.source) && other.end.abs > span.end.abs {
+            span.end = other.end;
+        }
+    };
+
+    if let Node::Element(element) = node {
+        for attribute in &element.attributes {
+            extend(&attribute.span);
+        }
+    }
+
+    for child in node.children() {
+        extend(&extent(child));
+    }
+
+    span
+}
+
+fn symbols(nodes: &[Node]) -> Vec<Value> {
+    let mut symbols = Vec::new();
+
+    for node in nodes {
+        #[rustfmt::skip]
+        let (name, kind, span) = match node {
+            Node::Element(element) => (&element.name, SYMBOL_STRUCT, &element.span),
+            Node::Instruction(Instruction { kind: ProcessingInstruction::Borrowable(borrowable), span, .. }) => (&borrowable.name, SYMBOL_NAMESPACE, span),
+            _ => continue,
+        };
+
+        let mut children = Vec::new();
+
+        if let Node::Element(element) = node {
+            #[rustfmt::skip]
+            children.extend(element.attributes.iter().map(|attribute| json!({
+                "kind": SYMBOL_PROPERTY,
+                "name": attribute.name,
+                "range": range(&attribute.span),
+                "selectionRange": range(&attribute.span),
+            })));
+        }
+
+        children.extend(self::symbols(node.children()));
+
+        symbols.push(json!({
+            "children": children,
+            "kind": kind,
+            "name": name,
+            "range": range(&extent(node)),
+            "selectionRange": range(span),
+        }));
+    }
+
+    symbols
+}
+
+/// A language server for Markup-Type documents, speaking JSON-RPC over any
+/// pair of streams (stdio in practice).
+///
+/// Documents are synchronized in full and analyzed with the [`Pipeline`] on
+/// every change; the server publishes their diagnostics, resolves a borrow to
+/// the element it refers to, describes elements and attributes from the
+/// schema and their doc comments on hover and lists the element tree as document symbols.
+pub struct Server<R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    documents: HashMap<String, Rc<Source>>,
+    /// The files each document includes that it last published diagnostics
+    /// for, to clear them once they are gone.
+    included: HashMap<String, Vec<String>>,
+    pipeline: Pipeline,
+    shutdown: bool,
+    transport: Transport<R, W>,
+}
+
+impl<R, W> Server<R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+
+        self.transport.write(&message)
+    }
+
+    fn respond(&mut self, id: Value, result: Value) -> io::Result<()> {
+        let message = json!({ "id": id, "jsonrpc": "2.0", "result": result });
+
+        self.transport.write(&message)
+    }
+
+    fn fail(&mut self, id: Value, code: i64, message: &str) -> io::Result<()> {
+        let error = json!({ "code": code, "message": message });
+        let message = json!({ "error": error, "id": id, "jsonrpc": "2.0" });
+
+        self.transport.write(&message)
+    }
+
+    fn analyze(&self, source: &Rc<Source>) -> (Document, Vec<Diagnostic>) {
+        Builder::new(self.pipeline.analyze(source)).build()
+    }
+
+    fn locate(&self, params: &Value) -> Option<At> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let source = self.documents.get(uri)?;
+
+        At::locate(source.clone(), &params["position"])
+    }
+}
+
+impl<R, W> Server<R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    /// Publishes the diagnostics of a document, and those of the files it
+    /// includes under their own URIs.
+    fn publish(&mut self, uri: &str) -> io::Result<()> {
+        let mut groups: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+
+        for included in self.included.remove(uri).into_iter().flatten() {
+            groups.insert(included, Vec::new());
+        }
+
+        if let Some(source) = self.documents.get(uri) {
+            for diagnostic in &self.analyze(source).1 {
+                #[rustfmt::skip]
+                groups.entry(self::uri(&diagnostic.span.source)).or_default().push(self::diagnostic(diagnostic));
+            }
+        }
+
+        let diagnostics = groups.remove(uri).unwrap_or_default();
+        let params = json!({ "diagnostics": diagnostics, "uri": uri });
+
+        self.notify("textDocument/publishDiagnostics", params)?;
+
+        let mut included = Vec::new();
+
+        for (other, diagnostics) in groups {
+            if !diagnostics.is_empty() {
+                included.push(other.clone());
+            }
+
+            let params = json!({ "diagnostics": diagnostics, "uri": other });
+
+            self.notify("textDocument/publishDiagnostics", params)?;
+        }
+
+        self.included.insert(uri.to_string(), included);
+
+        Ok(())
+    }
+
+    fn open(&mut self, params: &Value) -> io::Result<()> {
+        let document = &params["textDocument"];
+
+        let (Some(uri), Some(text)) = (document["uri"].as_str(), document["text"].as_str()) else {
+            return Ok(());
+        };
+
+        self.documents
+            .insert(uri.to_string(), Source::string(uri, text));
+
+        self.publish(uri)
+    }
+
+    fn change(&mut self, params: &Value) -> io::Result<()> {
+        let Some(uri) = params["textDocument"]["uri"].as_str() else {
+            return Ok(());
+        };
+
+        #[rustfmt::skip]
+        let Some(text) = params["contentChanges"].as_array().and_then(|changes| changes.last()).and_then(|change| change["text"].as_str()) else {
+            return Ok(());
+        };
+
+        self.documents
+            .insert(uri.to_string(), Source::string(uri, text));
+
+        self.publish(uri)
+    }
+
+    fn close(&mut self, params: &Value) -> io::Result<()> {
+        let Some(uri) = params["textDocument"]["uri"].as_str() else {
+            return Ok(());
+        };
+
+        self.documents.remove(uri);
+
+        self.publish(uri)
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let Some(at) = self.locate(params) else {
+            return Value::Null;
+        };
+
+        let (document, _) = self.analyze(&at.source);
+
+        match resolve(&document.children, &at, &mut Vec::new()) {
+            Some(element) => json!({
+                "range": range(&element.span),
+                "uri": uri(&element.span.source),
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let Some(at) = self.locate(params) else {
+            return Value::Null;
+        };
+
+        let schema = self.pipeline.schema.as_deref();
+        let (document, _) = self.analyze(&at.source);
+
+        #[rustfmt::skip]
+        let (value, span) = match target(&document.children, &at) {
+            Some(Target::Attribute(element, attribute)) => {
+                let Some(schema) = schema else {
+                    return Value::Null;
+                };
+
+                let value = match schema.attribute(&element.name, &attribute.name) {
+                    Some(declaration) => format!("**attribute** `{}` of `{}`\n\nexpects {}", attribute.name, element.name, describe_attribute(declaration)),
+                    None => format!("**attribute** `{}` of `{}`\n\nnot declared in the schema", attribute.name, element.name),
+                };
+
+                (value, &attribute.span)
+            }
+            Some(Target::Element(name, doc, span)) => {
+                let value = match (schema, doc) {
+                    (Some(schema), Some(doc)) => format!("{}\n\n{}", describe_element(schema, name), doc),
+                    (Some(schema), None) => describe_element(schema, name),
+                    (None, Some(doc)) => format!("**element** `{}`\n\n{}", name, doc),
+                    (None, None) => return Value::Null,
+                };
+
+                (value, span)
+            }
+            None => return Value::Null,
+        };
+
+        json!({
+            "contents": { "kind": "markdown", "value": value },
+            "range": range(span),
+        })
+    }
+
+    fn symbols(&self, params: &Value) -> Value {
+        #[rustfmt::skip]
+        let Some(source) = params["textDocument"]["uri"].as_str().and_then(|uri| self.documents.get(uri)) else {
+            return Value::Null;
+        };
+
+        let (document, _) = self.analyze(source);
+
+        Value::Array(symbols(&document.children))
+    }
+}
+
+impl<R, W> Server<R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    fn notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        match method {
+            "textDocument/didChange" => self.change(params),
+            "textDocument/didClose" => self.close(params),
+            "textDocument/didOpen" => self.open(params),
+            _ => Ok(()),
+        }
+    }
+
+    fn request(&mut self, id: Value, method: &str, params: &Value) -> io::Result<()> {
+        if self.shutdown {
+            return self.fail(id, INVALID_REQUEST, "the server is shutting down");
+        }
+
+        #[rustfmt::skip]
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "definitionProvider": true,
+                    "documentSymbolProvider": true,
+                    "hoverProvider": true,
+                    "textDocumentSync": 1,
+                },
+                "serverInfo": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "shutdown" => {
+                self.shutdown = true;
+
+                Value::Null
+            },
+            "textDocument/definition" => self.definition(params),
+            "textDocument/documentSymbol" => self.symbols(params),
+            "textDocument/hover" => self.hover(params),
+            _ => return self.fail(id, METHOD_NOT_FOUND, &format!("unknown method '{}'", method)),
+        };
+
+        self.respond(id, result)
+    }
+
+    /// Serves requests until the client sends `exit` or closes the stream, and
+    /// returns the exit code: 0 if `shutdown` was requested first, else 1.
+    pub fn run(&mut self) -> io::Result<u8> {
+        let code = |server: &Self| if server.shutdown { 0 } else { 1 };
+
+        loop {
+            let message = match self.transport.read() {
+                Ok(Some(message)) => message,
+                Ok(None) => return Ok(code(self)),
+                Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                    self.fail(Value::Null, PARSE_ERROR, &error.to_string())?;
+
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+
+            // Responses to requests of the server carry no method.
+            let Some(method) = message["method"].as_str() else {
+                continue;
+            };
+
+            #[rustfmt::skip]
+            match message.get("id") {
+                _ if method == "exit" => return Ok(code(self)),
+                Some(id) => self.request(id.clone(), method, &message["params"])?,
+                None => self.notification(method, &message["params"])?,
+            };
+        }
+    }
+}
+
+impl<R, W> Server<R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    pub fn new(reader: R, writer: W, pipeline: Pipeline) -> Self {
+        let transport = Transport::new(reader, writer).with_limit(pipeline.limits.bytes);
+
+        Self {
+            documents: HashMap::new(),
+            included: HashMap::new(),
+            pipeline,
+            shutdown: false,
+            transport,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io, rc::Rc};
+
+    use serde_json::{json, Value};
+
+    use crate::analyzer::{semantic::schema::Schema, source::Source, Pipeline};
+
+    use super::{transport::Transport, Server};
+
+    /// Plays the client: sends `messages` to a server and collects its replies.
+    fn exchange(pipeline: Pipeline, messages: &[Value]) -> io::Result<(u8, Vec<Value>)> {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        for message in messages {
+            Transport::new(io::empty(), &mut input).write(message)?;
+        }
+
+        let code = Server::new(input.as_slice(), &mut output, pipeline).run()?;
+
+        let mut transport = Transport::new(output.as_slice(), io::sink());
+        let mut replies = Vec::new();
+
+        while let Some(reply) = transport.read()? {
+            replies.push(reply);
+        }
+
+        Ok((code, replies))
+    }
+
+    #[test]
+    fn test() -> io::Result<()> {
+        let schema = Source::string(
+            "schema.mt",
+            "element | name \"nav\" { attribute | name \"class\" } element | name \"main\" | open",
+        );
+
+        let (schema, _) = Schema::parse(&schema);
+        let pipeline = Pipeline::with_schema(Rc::new(schema));
+
+        let uri = "file:///test.mt";
+        let text =
+            "layout -> {\n  nav | class \"x\"\n}\n## The page body.\nmain {\n  nav <-\n  li\n}\n";
+
+        let document = json!({ "uri": uri });
+        let position = |line: u32, character: u32| json!({ "position": { "character": character, "line": line }, "textDocument": document });
+
+        let (code, replies) = exchange(
+            pipeline.clone(),
+            &[
+                json!({ "id": 1, "method": "initialize", "params": {} }),
+                json!({ "method": "textDocument/didOpen", "params": { "textDocument": { "text": text, "uri": uri } } }),
+                json!({ "id": 2, "method": "textDocument/definition", "params": position(5, 3) }),
+                json!({ "id": 3, "method": "textDocument/hover", "params": position(1, 3) }),
+                json!({ "id": 4, "method": "textDocument/documentSymbol", "params": { "textDocument": document } }),
+                json!({ "id": 5, "method": "textDocument/hover", "params": position(4, 1) }),
+                json!({ "id": 6, "method": "shutdown" }),
+                json!({ "method": "exit" }),
+            ],
+        )?;
+
+        assert_eq!(code, 0);
+        assert_eq!(replies.len(), 7);
+        assert_eq!(replies[0]["result"]["capabilities"]["hoverProvider"], true);
+
+        let diagnostics = replies[1]["params"]["diagnostics"].as_array();
+
+        #[rustfmt::skip]
+        let undeclared = diagnostics.into_iter().flatten().find(|diagnostic| diagnostic["message"] == "the element 'li' is not declared in the schema");
+
+        assert_eq!(
+            undeclared.map(|diagnostic| &diagnostic["range"]["start"]),
+            Some(&json!({ "character": 2, "line": 6 }))
+        );
+
+        assert_eq!(
+            replies[2]["result"]["range"]["start"],
+            json!({ "character": 2, "line": 1 })
+        );
+
+        let hover = replies[3]["result"]["contents"]["value"]
+            .as_str()
+            .unwrap_or_default();
+
+        assert!(hover.starts_with("**element** `nav`"));
+        assert!(hover.contains("`class` (a string)"));
+
+        let symbols = &replies[4]["result"];
+
+        assert_eq!(symbols[0]["name"], "layout");
+        assert_eq!(symbols[0]["children"][0]["name"], "nav");
+        assert_eq!(symbols[0]["children"][0]["children"][0]["name"], "class");
+        assert_eq!(symbols[1]["name"], "main");
+
+        let hover = replies[5]["result"]["contents"]["value"]
+            .as_str()
+            .unwrap_or_default();
+
+        assert!(hover.starts_with("**element** `main`"));
+        assert!(hover.ends_with("\n\nThe page body."));
+
+        let (code, _) = exchange(pipeline.clone(), &[json!({ "method": "exit" })])?;
+
+        assert_eq!(code, 1);
+
+        let directory = std::env::temp_dir().join(format!("markup type {}", std::process::id()));
+
+        std::fs::create_dir_all(&directory)?;
+        std::fs::write(directory.join("header.mt"), "nav { li }")?;
+
+        let uri = |name: &str| {
+            let path = directory.join(name);
+
+            format!("file://{}", path.to_string_lossy().replace(' ', "%20"))
+        };
+
+        let (_, replies) = exchange(
+            pipeline,
+            &[
+                json!({ "method": "textDocument/didOpen", "params": { "textDocument": { "text": "main { \"header.mt\" <- }", "uri": uri("main.mt") } } }),
+            ],
+        )?;
+
+        std::fs::remove_dir_all(&directory)?;
+
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0]["params"]["uri"], uri("main.mt"));
+        assert_eq!(replies[0]["params"]["diagnostics"], json!([]));
+        assert_eq!(replies[1]["params"]["uri"], uri("header.mt"));
+        assert_eq!(
+            replies[1]["params"]["diagnostics"][0]["message"],
+            "the element 'li' is not declared in the schema"
+        );
+
+        let input = b"Content-Length: 99999999999\r\n\r\n{}";
+        let mut transport = Transport::new(&input[..], io::sink()).with_limit(1024);
+
+        #[rustfmt::skip]
+        assert!(transport.read().is_err_and(|error| error.kind() == io::ErrorKind::InvalidData));
+        assert!(transport.read()?.is_none());
+
+        Ok(())
+    }
+}
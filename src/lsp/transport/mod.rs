@@ -0,0 +1,109 @@
+use std::io::{self, BufRead, Read, Write};
+
+use serde_json::Value;
+
+use crate::analyzer::syntactic::limits::Limits;
+
+/// Reads and writes JSON-RPC messages framed by a `Content-Length` header, as
+/// the Language Server Protocol does over stdio.
+pub struct Transport<R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    /// The most bytes the content of a message can have.
+    limit: usize,
+    reader: R,
+    writer: W,
+}
+
+impl<R, W> Transport<R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    /// Reads the next message, or `None` once the stream is closed. A message
+    /// that is not valid JSON, or longer than the limit, is reported as
+    /// [`io::ErrorKind::InvalidData`]; a long one is skipped without being
+    /// held in memory.
+    pub fn read(&mut self) -> io::Result<Option<Value>> {
+        let mut length = None;
+
+        loop {
+            let mut line = String::new();
+
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+
+            let line = line.trim_end();
+
+            if line.is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+
+        let Some(length) = length else {
+            let message = "a message has no valid Content-Length header";
+
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        };
+
+        if length > self.limit {
+            io::copy(&mut (&mut self.reader).take(length as u64), &mut io::sink())?;
+
+            let message = format!(
+                "a message is {} bytes long, over the limit of {} bytes",
+                length, self.limit
+            );
+
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+
+        let mut content = vec![0; length];
+        self.reader.read_exact(&mut content)?;
+
+        serde_json::from_slice(&content)
+            .map(Some)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    pub fn write(&mut self, message: &Value) -> io::Result<()> {
+        let content = message.to_string();
+
+        write!(
+            self.writer,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )?;
+
+        self.writer.flush()
+    }
+}
+
+impl<R, W> Transport<R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            limit: Limits::default().bytes,
+            reader,
+            writer,
+        }
+    }
+
+    /// Rejects the messages longer than `limit` bytes.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+}
@@ -1 +1,535 @@
-fn main() {}
+use std::{process::ExitCode, rc::Rc};
+
+use markup_type::{
+    analyzer::{
+        diagnostic::{self, sarif, Diagnostic, Position, Span},
+        lexical::tokenizer,
+        semantic::schema::Schema,
+        source::{self, Source, SourceMap},
+        syntactic::{
+            self,
+            limits::Limits,
+            tree::{Builder, Node},
+        },
+        Pipeline,
+    },
+    emit::{format::Formatter, html},
+    lsp::Server,
+};
+
+const USAGE: &str = "\
+usage: markup-type-bin <command> [options] <file>...
+
+commands:
+    check [options] <file>...           analyze the files and print their diagnostics
+    build [options] <file>              analyze the file and emit it in another format
+    fmt [options] <file>...             rewrite the files in the canonical layout
+    lsp [options]                       serve the language server protocol over stdio
+    tokens <file>                       print the token stream of the file
+    tree [options] <file>               print the document tree of the file
+
+options:
+    --check                             make fmt report the files it would change instead of writing them
+    --diagnostics <style>               how diagnostics are written: text, plain, json or sarif (default: text)
+    --format <html>                     the output format of build (default: html)
+    --includes                          let the standard input and lsp documents include files
+    --max-attributes <count>            the most attributes an element can have (default: 256)
+    --max-bytes <count>                 the most bytes an input or included file can have (default: 16777216)
+    --max-depth <count>                 the deepest a node or an include can be nested (default: 256)
+    --max-includes <count>              the most files a document can include (default: 1024)
+    --max-length <count>                the most bytes a token or value can have (default: 65536)
+    --max-nodes <count>                 the most nodes a document can have (default: 1000000)
+    --output <path>                     write the output of build to a file instead of stdout
+    --schema <path>                     validate the elements against a schema
+    --strip-comments                    make build leave comments and doc comments out of the output
+    --width <columns>                   the line width fmt fits attributes into (default: 80)
+
+a <file> of '-' reads the standard input; diagnostics are written to the standard error.";
+
+#[derive(Default)]
+struct Options {
+    check: bool,
+    diagnostics: Option<String>,
+    format: Option<String>,
+    includes: bool,
+    max_attributes: Option<String>,
+    max_bytes: Option<String>,
+    max_depth: Option<String>,
+    max_includes: Option<String>,
+    max_length: Option<String>,
+    max_nodes: Option<String>,
+    output: Option<String>,
+    paths: Vec<String>,
+    schema: Option<String>,
+    strip_comments: bool,
+    width: Option<String>,
+}
+
+impl Options {
+    fn parse(arguments: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut arguments = arguments.iter();
+
+        while let Some(argument) = arguments.next() {
+            #[rustfmt::skip]
+            let option = match argument.as_str() {
+                "--diagnostics" => &mut options.diagnostics,
+                "--format" => &mut options.format,
+                "--max-attributes" => &mut options.max_attributes,
+                "--max-bytes" => &mut options.max_bytes,
+                "--max-depth" => &mut options.max_depth,
+                "--max-includes" => &mut options.max_includes,
+                "--max-length" => &mut options.max_length,
+                "--max-nodes" => &mut options.max_nodes,
+                "--output" => &mut options.output,
+                "--schema" => &mut options.schema,
+                "--width" => &mut options.width,
+                "--check" => {
+                    options.check = true;
+
+                    continue;
+                },
+                "--includes" => {
+                    options.includes = true;
+
+                    continue;
+                },
+                "--strip-comments" => {
+                    options.strip_comments = true;
+
+                    continue;
+                },
+                _ if argument.starts_with("--") => {
+                    return Err(format!("unknown option '{}'", argument));
+                },
+                _ => {
+                    options.paths.push(argument.clone());
+
+                    continue;
+                }
+            };
+
+            let value = arguments
+                .next()
+                .ok_or_else(|| format!("{} expects a value", argument))?;
+
+            *option = Some(value.clone());
+        }
+
+        Ok(options)
+    }
+
+    /// The limits of the pipeline, the defaults but for those given.
+    fn limits(&self) -> Result<Limits, String> {
+        fn parse<T: std::str::FromStr>(
+            value: &Option<String>,
+            limit: &mut T,
+        ) -> Result<(), String> {
+            if let Some(value) = value {
+                *limit = value
+                    .parse()
+                    .map_err(|_| format!("the limit '{}' is not a number", value))?;
+            }
+
+            Ok(())
+        }
+
+        let mut limits = Limits::default();
+
+        parse(&self.max_attributes, &mut limits.attributes)?;
+        parse(&self.max_bytes, &mut limits.bytes)?;
+        parse(&self.max_depth, &mut limits.depth)?;
+        parse(&self.max_includes, &mut limits.includes)?;
+        parse(&self.max_length, &mut limits.length)?;
+        parse(&self.max_nodes, &mut limits.nodes)?;
+
+        Ok(limits)
+    }
+
+    fn single(&self) -> Result<&str, String> {
+        match self.paths.as_slice() {
+            [path] => Ok(path),
+            [] => Err("no input file was given".into()),
+            _ => Err("only one input file was expected".into()),
+        }
+    }
+}
+
+/// How diagnostics are written: colored or plain text, one JSON object per
+/// line, or a single SARIF log once the command finishes.
+#[derive(Clone, Copy, Default)]
+enum Style {
+    Json,
+    Plain,
+    Sarif,
+    #[default]
+    Text,
+}
+
+#[derive(Default)]
+struct Report {
+    collected: Vec<Diagnostic>,
+    failed: bool,
+    /// Whether the standard input and the language server include files.
+    includes: bool,
+    pipeline: Pipeline,
+    sources: SourceMap,
+    style: Style,
+}
+
+impl Report {
+    fn new(options: &Options) -> Result<Self, String> {
+        let style = match options.diagnostics.as_deref() {
+            Some("json") => Style::Json,
+            Some("plain") => Style::Plain,
+            Some("sarif") => Style::Sarif,
+            Some("text") | None => Style::Text,
+            Some(style) => return Err(format!("unknown diagnostic style '{}'", style)),
+        };
+
+        let limits = options.limits()?;
+
+        Ok(Self {
+            includes: options.includes,
+            pipeline: Pipeline {
+                limits,
+                ..Pipeline::new()
+            },
+            sources: SourceMap::with_limit(limits.bytes),
+            style,
+            ..Self::default()
+        })
+    }
+
+    fn load_schema(&mut self, options: &Options) -> Result<(), String> {
+        let Some(path) = &options.schema else {
+            return Ok(());
+        };
+
+        let source = self.open(path)?;
+        let (schema, diagnostics) = Schema::parse(&source);
+
+        for diagnostic in &diagnostics {
+            self.diagnostic(diagnostic);
+        }
+
+        self.pipeline.schema = Some(Rc::new(schema));
+
+        Ok(())
+    }
+
+    fn open(&mut self, path: &str) -> Result<Rc<Source>, String> {
+        let (source, diagnostics) = self
+            .sources
+            .load(path)
+            .map_err(|error| format!("cannot read '{}': {}", path, error))?;
+
+        for diagnostic in &diagnostics {
+            self.diagnostic(diagnostic);
+        }
+
+        Ok(source)
+    }
+
+    /// The pipeline `source` is analyzed with; files include other files, the
+    /// standard input only with `--includes`.
+    fn pipeline(&self, source: &Source) -> Pipeline {
+        Pipeline {
+            includes: self.includes || !matches!(source.kind, source::Kind::Stdin),
+            ..self.pipeline.clone()
+        }
+    }
+
+    fn diagnostic(&mut self, diagnostic: &Diagnostic) {
+        if let diagnostic::Kind::Critical | diagnostic::Kind::Error = diagnostic.kind {
+            self.failed = true;
+        }
+
+        match self.style {
+            Style::Json => match serde_json::to_string(diagnostic) {
+                Ok(line) => eprintln!("{}", line),
+                Err(error) => eprintln!("error: cannot serialize a diagnostic: {}", error),
+            },
+            Style::Plain => eprintln!("{}\n", diagnostic.plain()),
+            Style::Sarif => self.collected.push(diagnostic.clone()),
+            Style::Text => eprintln!("{}\n", diagnostic),
+        }
+    }
+
+    /// Writes the diagnostics held back until the end.
+    fn finish(&self) {
+        if let Style::Sarif = self.style {
+            eprintln!("{:#}", sarif::log(&self.collected));
+        }
+    }
+
+    fn exit_code(&self) -> ExitCode {
+        if self.failed {
+            ExitCode::from(1)
+        } else {
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+fn check(options: &Options, report: &mut Report) -> Result<(), String> {
+    if options.paths.is_empty() {
+        return Err("no input files were given".into());
+    }
+
+    for path in &options.paths {
+        let source = report.open(path)?;
+
+        for result in report.pipeline(&source).analyze(&source) {
+            if let syntactic::Result::Diagnostic(diagnostic) = result {
+                report.diagnostic(&diagnostic);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn build(options: &Options, report: &mut Report) -> Result<(), String> {
+    let path = options.single()?;
+    let format = options.format.as_deref().unwrap_or("html");
+
+    if format != "html" {
+        return Err(format!("unknown output format '{}'", format));
+    }
+
+    let source = report.open(path)?;
+    let (document, diagnostics) = Builder::new(report.pipeline(&source).analyze(&source)).build();
+
+    for diagnostic in &diagnostics {
+        report.diagnostic(diagnostic);
+    }
+
+    if report.failed {
+        return Ok(());
+    }
+
+    let (content, diagnostics) = html::Emitter::new()
+        .with_comments(!options.strip_comments)
+        .emit(&document);
+
+    for diagnostic in &diagnostics {
+        report.diagnostic(diagnostic);
+    }
+
+    match &options.output {
+        Some(output) => std::fs::write(output, content)
+            .map_err(|error| format!("cannot write '{}': {}", output, error))?,
+        None => println!("{}", content),
+    };
+
+    Ok(())
+}
+
+fn fmt(options: &Options, report: &mut Report) -> Result<(), String> {
+    if options.paths.is_empty() {
+        return Err("no input files were given".into());
+    }
+
+    #[rustfmt::skip]
+    let width = match &options.width {
+        Some(width) => width.parse().map_err(|_| format!("the width '{}' is not a number", width))?,
+        None => 80,
+    };
+
+    let formatter = Formatter::new(width);
+
+    for path in &options.paths {
+        let source = report.open(path)?;
+
+        let formatted = match formatter.format(&source) {
+            Ok(formatted) => formatted,
+            Err(diagnostics) => {
+                for diagnostic in &diagnostics {
+                    report.diagnostic(diagnostic);
+                }
+
+                continue;
+            }
+        };
+
+        if options.check {
+            if formatted != source.text {
+                let span = Span::new(source.clone(), Position::default(), Position::default());
+
+                let diagnostic =
+                    Diagnostic::new(diagnostic::Kind::Error, "the file is not formatted", span)
+                        .with_help("run `fmt` without `--check` to rewrite it");
+
+                report.diagnostic(&diagnostic);
+            }
+        } else if path == "-" {
+            print!("{}", formatted);
+        } else if formatted != source.text {
+            std::fs::write(path, formatted)
+                .map_err(|error| format!("cannot write '{}': {}", path, error))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn lsp(_: &Options, report: &mut Report) -> Result<(), String> {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+
+    let pipeline = Pipeline {
+        includes: report.includes,
+        ..report.pipeline.clone()
+    };
+
+    let code = Server::new(stdin.lock(), stdout.lock(), pipeline)
+        .run()
+        .map_err(|error| format!("the language server stopped: {}", error))?;
+
+    // The client exited without asking the server to shut down first.
+    report.failed |= code != 0;
+
+    Ok(())
+}
+
+fn tokens(options: &Options, report: &mut Report) -> Result<(), String> {
+    let path = options.single()?;
+
+    let source = report.open(path)?;
+
+    for token in tokenizer::Analyzer::new(source.clone(), source.chars()) {
+        let start = token.span.start;
+        let end = token.span.end;
+
+        println!(
+            "{}:{}-{}:{}\t{:?}",
+            start.row + 1,
+            start.col + 1,
+            end.row + 1,
+            end.col + 1,
+            token.kind
+        );
+    }
+
+    Ok(())
+}
+
+fn print_node(node: &Node, depth: usize) {
+    let indentation = "    ".repeat(depth);
+
+    match node {
+        Node::Comment(comment) => {
+            println!("{}comment {:?}", indentation, comment.value);
+        }
+        Node::Element(element) => {
+            println!("{}element {}", indentation, element.name);
+
+            if let Some(doc) = &element.doc {
+                println!("{}    doc {:?}", indentation, doc);
+            }
+
+            for attribute in &element.attributes {
+                match &attribute.value {
+                    Some(syntactic::Value::String(value)) => {
+                        println!(
+                            "{}    attribute {} = {:?}",
+                            indentation, attribute.name, value
+                        )
+                    }
+                    Some(value) => {
+                        println!(
+                            "{}    attribute {} = {}",
+                            indentation, attribute.name, value
+                        )
+                    }
+                    None => println!("{}    attribute {}", indentation, attribute.name),
+                }
+            }
+        }
+        Node::Instruction(instruction) => {
+            #[rustfmt::skip]
+            let (kind, name) = match &instruction.kind {
+                syntactic::ProcessingInstruction::Borrow(borrow) => ("borrow", &borrow.name),
+                syntactic::ProcessingInstruction::Borrowable(borrowable) => ("borrowable", &borrowable.name),
+                syntactic::ProcessingInstruction::Include(include) => ("include", &include.path),
+            };
+
+            println!("{}{} {}", indentation, kind, name);
+        }
+        Node::Text(text) => {
+            println!("{}text {:?}", indentation, text.value);
+        }
+    }
+
+    for child in node.children() {
+        print_node(child, depth + 1);
+    }
+}
+
+fn tree(options: &Options, report: &mut Report) -> Result<(), String> {
+    let path = options.single()?;
+
+    let source = report.open(path)?;
+    let (document, diagnostics) = Builder::new(report.pipeline(&source).analyze(&source)).build();
+
+    for diagnostic in &diagnostics {
+        report.diagnostic(diagnostic);
+    }
+
+    for node in &document.children {
+        print_node(node, 0);
+    }
+
+    Ok(())
+}
+
+fn run(arguments: &[String]) -> Result<ExitCode, String> {
+    let Some((command, arguments)) = arguments.split_first() else {
+        return Err("no command was given".into());
+    };
+
+    let options = Options::parse(arguments)?;
+
+    #[rustfmt::skip]
+    let command: fn(&Options, &mut Report) -> Result<(), String> = match command.as_str() {
+        "check" => check,
+        "build" => build,
+        "fmt" => fmt,
+        "lsp" => lsp,
+        "tokens" => tokens,
+        "tree" => tree,
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => return Err(format!("unknown command '{}'", command)),
+    };
+
+    let mut report = Report::new(&options)?;
+
+    // The report is written on every way out, so a command that stops early
+    // keeps the diagnostics it found so far.
+    let result = report
+        .load_schema(&options)
+        .and_then(|()| command(&options, &mut report));
+
+    report.finish();
+    result?;
+
+    Ok(report.exit_code())
+}
+
+fn main() -> ExitCode {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+
+    match run(&arguments) {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+
+            ExitCode::from(2)
+        }
+    }
+}